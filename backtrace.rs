@@ -0,0 +1,521 @@
+// Copyright 2017 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Backtrace capture and symbolization.
+//!
+//! A [`Backtrace`] records the instruction pointers of the current call
+//! stack through [`Std::trace`]. Capturing never allocates, so it is safe to
+//! use from a panic hook or right before [`util::abort`].
+//!
+//! Addresses are resolved to function names by a [`Symbolizer`], which reads
+//! the symbol table of an ELF image through [`fs::File`]. Lookups stream the
+//! symbol table through a fixed-size buffer instead of loading it.
+//!
+//! ```ignore
+//! panic::set_hook(Box::new(|info| {
+//!     panic::default_hook::<MyPlatform>(info);
+//!     backtrace::print::<MyPlatform>(Some(Path::new("/bin/app")));
+//! }));
+//! ```
+//!
+//! [`Backtrace`]: struct.Backtrace.html
+//! [`Symbolizer`]: struct.Symbolizer.html
+//! [`Std::trace`]: ../traits/trait.Std.html#method.trace
+//! [`util::abort`]: ../util/fn.abort.html
+//! [`fs::File`]: ../fs/struct.File.html
+
+use prelude::*;
+use traits::Std;
+use marker::PhantomData;
+
+use cmp;
+use fmt;
+use fs::File;
+use io::{self, Read, Seek, SeekFrom, Write};
+use path::Path;
+use str;
+
+/// Maximum number of frames recorded by a [`Backtrace`].
+///
+/// [`Backtrace`]: struct.Backtrace.html
+pub const MAX_FRAMES: usize = 64;
+
+/// A captured call stack.
+pub struct Backtrace<STD: Std> {
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+    _std: PhantomData<STD>,
+}
+
+impl<STD: Std> Backtrace<STD> {
+    /// Captures the call stack of the current thread.
+    ///
+    /// At most [`MAX_FRAMES`] frames are recorded, innermost first. The
+    /// first frames belong to the capturing machinery itself.
+    ///
+    /// [`MAX_FRAMES`]: constant.MAX_FRAMES.html
+    #[inline(never)]
+    pub fn capture() -> Backtrace<STD> {
+        let mut bt = Backtrace {
+            frames: [0; MAX_FRAMES],
+            len: 0,
+            _std: PhantomData,
+        };
+        {
+            let frames = &mut bt.frames;
+            let len = &mut bt.len;
+            STD::trace(&mut |ip| {
+                frames[*len] = ip;
+                *len += 1;
+                *len < MAX_FRAMES
+            });
+        }
+        bt
+    }
+
+    /// Returns the instruction pointers of the captured frames, innermost
+    /// first.
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+
+    /// Writes the backtrace to `w`, one frame per line, resolving addresses
+    /// through `symbolizer` if given.
+    pub fn write_to<W: Write<STD> + ?Sized>(&self, w: &mut W,
+                                            mut symbolizer: Option<&mut Symbolizer<STD>>)
+                                            -> io::Result<(), STD> {
+        writeln!(w, "stack backtrace:")?;
+        for (i, &ip) in self.frames().iter().enumerate() {
+            // Return addresses point after the call, so look up the byte
+            // before to stay inside the calling function.
+            let symbol = match symbolizer {
+                Some(ref mut s) => s.resolve(ip.saturating_sub(1)).unwrap_or(None),
+                None => None,
+            };
+            match symbol {
+                Some(ref symbol) => {
+                    writeln!(w, "  {:>2}: {:#018x} - {}+{:#x}",
+                             i, ip, symbol.name(), ip - symbol.address())?
+                }
+                None => writeln!(w, "  {:>2}: {:#018x} - <unknown>", i, ip)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<STD: Std> fmt::Debug for Backtrace<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.frames().iter().map(|ip| Address(*ip)))
+            .finish()
+    }
+}
+
+struct Address(usize);
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// Captures the current backtrace and prints it to the platform's standard
+/// error, symbolized against the ELF image at `image` if given.
+///
+/// Errors while symbolizing are ignored and the affected frames are printed
+/// as raw addresses.
+pub fn print<STD: Std>(image: Option<&Path<STD>>) {
+    let bt = Backtrace::<STD>::capture();
+    let mut symbolizer = image.and_then(|image| Symbolizer::open(image).ok());
+    let _ = STD::Stderr::new().map(|mut stderr| bt.write_to(&mut stderr, symbolizer.as_mut()));
+}
+
+/// Walks the frame pointer chain of the current thread.
+///
+/// Backends can implement [`Std::trace`] with this when all code is
+/// compiled with frame pointers; otherwise it follows garbage pointers,
+/// hence `unsafe`. It yields no frames on architectures it doesn't know how
+/// to read the frame pointer on.
+///
+/// [`Std::trace`]: ../traits/trait.Std.html#method.trace
+#[inline(never)]
+pub unsafe fn trace_frame_pointers(cb: &mut FnMut(usize) -> bool) {
+    let word = ::mem::size_of::<usize>();
+    let mut fp = frame_pointer();
+    while fp != 0 && fp % word == 0 {
+        // Every frame starts with the caller's frame pointer, followed by
+        // the return address.
+        let next = *(fp as *const usize);
+        let ip = *((fp + word) as *const usize);
+        if ip == 0 || !cb(ip) {
+            break;
+        }
+        // The stack grows downwards, anything else is a corrupt chain.
+        if next <= fp {
+            break;
+        }
+        fp = next;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn frame_pointer() -> usize {
+    let fp;
+    asm!("mov %rbp, $0" : "=r"(fp));
+    fp
+}
+
+#[cfg(target_arch = "x86")]
+#[inline(always)]
+unsafe fn frame_pointer() -> usize {
+    let fp;
+    asm!("mov %ebp, $0" : "=r"(fp));
+    fp
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+unsafe fn frame_pointer() -> usize {
+    let fp;
+    asm!("mov $0, x29" : "=r"(fp));
+    fp
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "x86", target_arch = "aarch64")))]
+#[inline(always)]
+unsafe fn frame_pointer() -> usize {
+    0
+}
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+const STT_FUNC: u8 = 2;
+const SYM_SIZE: usize = 24;
+const MAX_NAME: usize = 256;
+
+/// Resolves addresses against the symbol table of a 64-bit little-endian
+/// ELF image.
+pub struct Symbolizer<STD: Std> {
+    file: File<STD>,
+    symtab_offset: u64,
+    symtab_size: u64,
+    strtab_offset: u64,
+    strtab_size: u64,
+    bias: usize,
+}
+
+impl<STD: Std> Symbolizer<STD> {
+    /// Opens the ELF image at `path` and locates its symbol table.
+    ///
+    /// `.symtab` is preferred over `.dynsym` if both are present.
+    pub fn open<P: AsRef<Path<STD>>>(path: P) -> io::Result<Symbolizer<STD>, STD> {
+        let mut file = File::open(path)?;
+
+        let mut ehdr = [0u8; 64];
+        read_exact_at(&mut file, 0, &mut ehdr)?;
+        if &ehdr[..4] != b"\x7fELF" {
+            return Err(const_error!(io::ErrorKind::InvalidData, "not an ELF image"));
+        }
+        if ehdr[4] != 2 || ehdr[5] != 1 {
+            return Err(const_error!(io::ErrorKind::InvalidData,
+                                    "only 64-bit little-endian ELF images are supported"));
+        }
+        let shoff = le64(&ehdr[40..]);
+        let shentsize = le16(&ehdr[58..]) as u64;
+        let shnum = le16(&ehdr[60..]) as u64;
+        if shentsize < 64 {
+            return Err(const_error!(io::ErrorKind::InvalidData,
+                                    "malformed ELF section header table"));
+        }
+
+        let mut found = None;
+        for i in 0..shnum {
+            let mut shdr = [0u8; 64];
+            read_exact_at(&mut file, section_offset(shoff, i, shentsize)?, &mut shdr)?;
+            let kind = le32(&shdr[4..]);
+            if kind != SHT_SYMTAB && kind != SHT_DYNSYM {
+                continue;
+            }
+            let section = (kind, le64(&shdr[24..]), le64(&shdr[32..]), le32(&shdr[40..]) as u64);
+            match found {
+                Some((SHT_SYMTAB, ..)) => {}
+                _ => found = Some(section),
+            }
+        }
+        let (_, symtab_offset, symtab_size, strtab_index) = match found {
+            Some(section) => section,
+            None => return Err(const_error!(io::ErrorKind::InvalidData,
+                                            "ELF image has no symbol table")),
+        };
+
+        let mut shdr = [0u8; 64];
+        read_exact_at(&mut file, section_offset(shoff, strtab_index, shentsize)?, &mut shdr)?;
+
+        Ok(Symbolizer {
+            file,
+            symtab_offset,
+            symtab_size,
+            strtab_offset: le64(&shdr[24..]),
+            strtab_size: le64(&shdr[32..]),
+            bias: 0,
+        })
+    }
+
+    /// Sets the difference between runtime addresses and the addresses in
+    /// the image, for position-independent images.
+    pub fn set_load_bias(&mut self, bias: usize) {
+        self.bias = bias;
+    }
+
+    /// Finds the function symbol containing `addr`.
+    pub fn resolve(&mut self, addr: usize) -> io::Result<Option<Symbol>, STD> {
+        let addr = match addr.checked_sub(self.bias) {
+            Some(addr) => addr as u64,
+            None => return Ok(None),
+        };
+
+        let mut best: Option<(u32, u64)> = None;
+        let mut buf = [0u8; SYM_SIZE * 64];
+        let mut pos = 0;
+        while pos < self.symtab_size {
+            let len = cmp::min(buf.len() as u64, self.symtab_size - pos) as usize;
+            let len = len - len % SYM_SIZE;
+            if len == 0 {
+                break;
+            }
+            let offset = match self.symtab_offset.checked_add(pos) {
+                Some(offset) => offset,
+                None => return Err(malformed()),
+            };
+            read_exact_at(&mut self.file, offset, &mut buf[..len])?;
+            for sym in buf[..len].chunks(SYM_SIZE) {
+                let value = le64(&sym[8..]);
+                let size = le64(&sym[16..]);
+                if sym[4] & 0xf != STT_FUNC || value > addr {
+                    continue;
+                }
+                // A symbol reaching past the end of the address space is
+                // malformed; don't let it match anything.
+                let end = match value.checked_add(cmp::max(size, 1)) {
+                    Some(end) => end,
+                    None => continue,
+                };
+                if addr < end {
+                    match best {
+                        Some((_, v)) if v >= value => {}
+                        _ => best = Some((le32(sym), value)),
+                    }
+                }
+            }
+            pos += len as u64;
+        }
+
+        let (name_offset, value) = match best {
+            Some(best) => best,
+            None => return Ok(None),
+        };
+        let mut symbol = Symbol {
+            name: [0; MAX_NAME],
+            name_len: 0,
+            address: value as usize + self.bias,
+        };
+        let name_offset = name_offset as u64;
+        if name_offset < self.strtab_size {
+            let len = cmp::min(MAX_NAME as u64, self.strtab_size - name_offset) as usize;
+            let offset = match self.strtab_offset.checked_add(name_offset) {
+                Some(offset) => offset,
+                None => return Err(malformed()),
+            };
+            read_exact_at(&mut self.file, offset, &mut symbol.name[..len])?;
+            symbol.name_len = symbol.name[..len].iter()
+                .position(|&b| b == 0)
+                .unwrap_or(len);
+        }
+        Ok(Some(symbol))
+    }
+}
+
+// The offset of section header `index`, failing on tables that don't fit in
+// a 64-bit file.
+fn section_offset<STD: Std>(shoff: u64, index: u64, shentsize: u64) -> io::Result<u64, STD> {
+    index.checked_mul(shentsize)
+        .and_then(|rel| shoff.checked_add(rel))
+        .ok_or_else(malformed)
+}
+
+fn malformed<STD: Std>() -> io::Error<STD> {
+    const_error!(io::ErrorKind::InvalidData, "malformed ELF image")
+}
+
+impl<STD: Std> fmt::Debug for Symbolizer<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Symbolizer")
+            .field("file", &self.file)
+            .field("bias", &self.bias)
+            .finish()
+    }
+}
+
+/// A resolved function symbol.
+///
+/// Names longer than 256 bytes are truncated.
+pub struct Symbol {
+    name: [u8; MAX_NAME],
+    name_len: usize,
+    address: usize,
+}
+
+impl Symbol {
+    /// Returns the raw (possibly mangled) symbol name.
+    pub fn raw_name(&self) -> &[u8] {
+        &self.name[..self.name_len]
+    }
+
+    /// Returns the symbol name, demangling Rust symbols when displayed.
+    pub fn name(&self) -> SymbolName {
+        SymbolName(self.raw_name())
+    }
+
+    /// Returns the runtime address of the start of the function.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Symbol")
+            .field("name", &self.name())
+            .field("address", &Address(self.address))
+            .finish()
+    }
+}
+
+/// A symbol name, displayed demangled if it is a Rust symbol.
+pub struct SymbolName<'a>(&'a [u8]);
+
+impl<'a> SymbolName<'a> {
+    /// Returns the raw symbol name.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+}
+
+impl<'a> fmt::Display for SymbolName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match demangle(self.0, f) {
+            Some(result) => result,
+            None => {
+                for &b in self.0 {
+                    let c = if b.is_ascii() { b as char } else { '\u{FFFD}' };
+                    fmt::Write::write_char(f, c)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Debug for SymbolName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+// Writes a legacy-mangled (`_ZN...E`) Rust symbol as `a::b::c`, dropping the
+// trailing hash. Returns `None` without writing anything if the name is not
+// in that format.
+fn demangle(name: &[u8], f: &mut fmt::Formatter) -> Option<fmt::Result> {
+    let inner = if name.starts_with(b"_ZN") && name.ends_with(b"E") {
+        &name[3..name.len() - 1]
+    } else {
+        return None;
+    };
+
+    // Validate the whole name before writing anything.
+    let mut segments = 0;
+    let mut rest = inner;
+    while !rest.is_empty() {
+        let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        let len: usize = str::from_utf8(&rest[..digits]).ok()?.parse().ok()?;
+        // A huge length prefix must not overflow; this runs while panicking.
+        let end = digits.checked_add(len)?;
+        if len == 0 || rest.len() < end {
+            return None;
+        }
+        str::from_utf8(&rest[digits..end]).ok()?;
+        rest = &rest[end..];
+        segments += 1;
+    }
+
+    let mut rest = inner;
+    let mut i = 0;
+    while !rest.is_empty() {
+        let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        let len: usize = str::from_utf8(&rest[..digits]).unwrap().parse().unwrap();
+        let segment = str::from_utf8(&rest[digits..digits + len]).unwrap();
+        rest = &rest[digits + len..];
+        i += 1;
+        let is_hash = i == segments && segment.len() == 17 && segment.starts_with('h') &&
+                      segment[1..].bytes().all(|b| b.is_ascii_hexdigit());
+        if is_hash {
+            break;
+        }
+        if i > 1 {
+            if let Err(e) = f.write_str("::") {
+                return Some(Err(e));
+            }
+        }
+        if let Err(e) = f.write_str(segment) {
+            return Some(Err(e));
+        }
+    }
+    Some(Ok(()))
+}
+
+fn read_exact_at<STD: Std>(file: &mut File<STD>, offset: u64, buf: &mut [u8])
+                           -> io::Result<(), STD> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+fn le16(b: &[u8]) -> u16 {
+    b[0] as u16 | (b[1] as u16) << 8
+}
+
+fn le32(b: &[u8]) -> u32 {
+    le16(b) as u32 | (le16(&b[2..]) as u32) << 16
+}
+
+fn le64(b: &[u8]) -> u64 {
+    le32(b) as u64 | (le32(&b[4..]) as u64) << 32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolName;
+
+    #[test]
+    fn demangle_legacy() {
+        assert_eq!(format!("{}", SymbolName(b"_ZN3foo3bar17h0123456789abcdefE")), "foo::bar");
+        assert_eq!(format!("{}", SymbolName(b"_ZN3foo3barE")), "foo::bar");
+        assert_eq!(format!("{}", SymbolName(b"main")), "main");
+    }
+
+    #[test]
+    fn demangle_malformed() {
+        assert_eq!(format!("{}", SymbolName(b"_ZN5fooE")), "_ZN5fooE");
+        assert_eq!(format!("{}", SymbolName(b"_ZN0E")), "_ZN0E");
+        let huge = b"_ZN18446744073709551615fooE";
+        assert_eq!(format!("{}", SymbolName(huge)), "_ZN18446744073709551615fooE");
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2012-2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Inspection and manipulation of the process's environment.
+//!
+//! Environment variables are read through the platform's [`Std::getenv`].
+//!
+//! [`Std::getenv`]: ../traits/trait.Std.html#method.getenv
+
+// #![stable(feature = "env", since = "1.0.0")]
+
+use prelude::*;
+use traits::Std;
+
+use error::Error;
+use ffi::{OsStr, OsString};
+use fmt;
+use path::PathBuf;
+
+/// Fetches the environment variable `key` from the current process.
+///
+/// # Errors
+///
+/// * Environment variable is not present
+/// * Environment variable is not valid unicode
+// #[stable(feature = "env", since = "1.0.0")]
+pub fn var<K: AsRef<OsStr<STD>>, STD: Std>(key: K) -> Result<String, VarError<STD>> {
+    match var_os(key) {
+        Some(s) => s.into_string().map_err(VarError::NotUnicode),
+        None => Err(VarError::NotPresent),
+    }
+}
+
+/// Fetches the environment variable `key` from the current process, returning
+/// [`None`] if the variable isn't set.
+///
+/// [`None`]: ../option/enum.Option.html#variant.None
+// #[stable(feature = "env", since = "1.0.0")]
+pub fn var_os<K: AsRef<OsStr<STD>>, STD: Std>(key: K) -> Option<OsString<STD>> {
+    let key = key.as_ref();
+    STD::getenv(key).unwrap_or_else(|e| {
+        panic!("failed to get environment variable `{:?}`: {}", key, e)
+    })
+}
+
+/// Returns the directory for temporary files, as reported by the platform's
+/// [`Std::temp_dir`].
+///
+/// The directory may not exist or be writable; nothing here checks.
+///
+/// [`Std::temp_dir`]: ../traits/trait.Std.html#method.temp_dir
+pub fn temp_dir<STD: Std>() -> PathBuf<STD> {
+    STD::temp_dir()
+}
+
+/// The error type for operations interacting with environment variables.
+/// Possibly returned from the [`env::var`] function.
+///
+/// [`env::var`]: fn.var.html
+#[derive(Debug, PartialEq, Eq, Clone)]
+// #[stable(feature = "env", since = "1.0.0")]
+pub enum VarError<STD: Std> {
+    /// The specified environment variable was not present in the current
+    /// process's environment.
+    // #[stable(feature = "env", since = "1.0.0")]
+    NotPresent,
+
+    /// The specified environment variable was found, but it did not contain
+    /// valid unicode data. The found data is returned as a payload of this
+    /// variant.
+    // #[stable(feature = "env", since = "1.0.0")]
+    NotUnicode(OsString<STD>),
+}
+
+// #[stable(feature = "env", since = "1.0.0")]
+impl<STD: Std> fmt::Display for VarError<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VarError::NotPresent => write!(f, "environment variable not found"),
+            VarError::NotUnicode(ref s) => {
+                write!(f, "environment variable was not valid unicode: {:?}", s)
+            }
+        }
+    }
+}
+
+// #[stable(feature = "env", since = "1.0.0")]
+impl<STD: Std> Error for VarError<STD> {
+    fn description(&self) -> &str {
+        match *self {
+            VarError::NotPresent => "environment variable not found",
+            VarError::NotUnicode(..) => "environment variable was not valid unicode",
+        }
+    }
+}
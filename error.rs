@@ -155,6 +155,17 @@ pub trait Error: Debug + Display {
     #[stable(feature = "rust1", since = "1.0.0")]
     fn cause(&self) -> Option<&Error> { None }
 
+    /// The lower-level source of this error, if any.
+    ///
+    /// This is the method to override for new error types; the default
+    /// returns [`cause`], so errors that only implement `cause` still form a
+    /// chain. Use [`chain`] to walk every source in turn.
+    ///
+    /// [`cause`]: trait.Error.html#method.cause
+    /// [`chain`]: trait.Error.html#method.chain
+    #[unstable(feature = "error_source", issue = "0")]
+    fn source(&self) -> Option<&Error> { self.cause() }
+
     /// Get the `TypeId` of `self`
     #[doc(hidden)]
     #[unstable(feature = "error_type_id",
@@ -336,6 +347,10 @@ impl<T: Error> Error for Box<T> {
     fn cause(&self) -> Option<&Error> {
         Error::cause(&**self)
     }
+
+    fn source(&self) -> Option<&Error> {
+        Error::source(&**self)
+    }
 }
 
 #[stable(feature = "fmt_error", since = "1.11.0")]
@@ -397,6 +412,28 @@ impl Error + 'static {
         t == boxed
     }
 
+    /// Returns an iterator starting with `self` and continuing with each
+    /// error's [`source`].
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use error::Error;
+    ///
+    /// fn print_chain(err: &(Error + 'static)) {
+    ///     for (i, e) in err.chain().enumerate() {
+    ///         println!("{}: {}", i, e);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`source`]: trait.Error.html#method.source
+    #[unstable(feature = "error_iter", issue = "0")]
+    #[inline]
+    pub fn chain(&self) -> Chain {
+        Chain { next: Some(self) }
+    }
+
     /// Returns some reference to the boxed value if it is of type `T`, or
     /// `None` if it isn't.
     #[stable(feature = "error_downcast", since = "1.3.0")]
@@ -434,6 +471,13 @@ impl Error + 'static + Send {
         <Error + 'static>::is::<T>(self)
     }
 
+    /// Forwards to the method defined on `Error + 'static`.
+    #[unstable(feature = "error_iter", issue = "0")]
+    #[inline]
+    pub fn chain(&self) -> Chain {
+        <Error + 'static>::chain(self)
+    }
+
     /// Forwards to the method defined on the type `Any`.
     #[stable(feature = "error_downcast", since = "1.3.0")]
     #[inline]
@@ -457,6 +501,13 @@ impl Error + 'static + Send + Sync {
         <Error + 'static>::is::<T>(self)
     }
 
+    /// Forwards to the method defined on `Error + 'static`.
+    #[unstable(feature = "error_iter", issue = "0")]
+    #[inline]
+    pub fn chain(&self) -> Chain {
+        <Error + 'static>::chain(self)
+    }
+
     /// Forwards to the method defined on the type `Any`.
     #[stable(feature = "error_downcast", since = "1.3.0")]
     #[inline]
@@ -516,10 +567,171 @@ impl Error + Send + Sync {
     }
 }
 
+/// An iterator over an error and its sources.
+///
+/// This struct is created by the [`chain`] method on `Error`.
+///
+/// [`chain`]: trait.Error.html#method.chain
+#[derive(Clone, Debug)]
+#[unstable(feature = "error_iter", issue = "0")]
+pub struct Chain<'a> {
+    next: Option<&'a Error>,
+}
+
+#[unstable(feature = "error_iter", issue = "0")]
+impl<'a> Iterator for Chain<'a> {
+    type Item = &'a Error;
+
+    fn next(&mut self) -> Option<&'a Error> {
+        let current = self.next;
+        self.next = current.and_then(|e| e.source());
+        current
+    }
+}
+
+/// Formats an error together with its chain of sources.
+///
+/// By default the chain is printed on a single line, separated by `: `.
+/// With [`pretty`] each source gets a line of its own, below the error:
+///
+/// ```text
+/// failed to open `config.toml`
+///
+/// Caused by:
+///       0: entity not found
+/// ```
+///
+/// `Debug` prints the same as `Display`, so a `Report` returned from `main`
+/// shows the full chain.
+///
+/// # Examples
+///
+/// ```ignore
+/// use error::Report;
+/// use fs;
+///
+/// if let Err(e) = fs::read::<_, MyPlatform>("config.toml") {
+///     println!("error: {}", Report::new(e));
+/// }
+/// ```
+///
+/// [`pretty`]: struct.Report.html#method.pretty
+#[unstable(feature = "error_reporter", issue = "0")]
+pub struct Report<E> {
+    error: E,
+    pretty: bool,
+}
+
+#[unstable(feature = "error_reporter", issue = "0")]
+impl<E: Error> Report<E> {
+    /// Creates a report printing `error` on a single line.
+    pub fn new(error: E) -> Report<E> {
+        Report {
+            error: error,
+            pretty: false,
+        }
+    }
+
+    /// Sets whether the chain is printed on multiple lines.
+    pub fn pretty(mut self, pretty: bool) -> Report<E> {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Returns the reported error.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Unwraps the report, returning the error.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+
+    // The messages of the error and its sources, leaving out a source whose
+    // message was already shown as (the end of) the one before, as an
+    // `io::Error` shows its payload's.
+    fn messages(&self) -> Vec<String> {
+        let mut messages = Vec::new();
+        messages.push(fmt::format(format_args!("{}", self.error)));
+        let mut source = self.error.source();
+        while let Some(e) = source {
+            let message = fmt::format(format_args!("{}", e));
+            let repeated = {
+                let last = &messages[messages.len() - 1];
+                *last == message ||
+                    (last.ends_with(&message[..]) &&
+                     last[..last.len() - message.len()].ends_with(": "))
+            };
+            if !repeated {
+                messages.push(message);
+            }
+            source = e.source();
+        }
+        messages
+    }
+
+    fn fmt_single(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, message) in self.messages().iter().enumerate() {
+            if i > 0 {
+                f.write_str(": ")?;
+            }
+            f.write_str(message)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_multi(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages = self.messages();
+        f.write_str(&messages[0])?;
+        if messages.len() > 1 {
+            write!(f, "\n\nCaused by:")?;
+        }
+        for (i, message) in messages[1..].iter().enumerate() {
+            write!(f, "\n{:>7}: ", i)?;
+            // Indent the continuation lines of multi-line messages.
+            for (j, line) in message.lines().enumerate() {
+                if j > 0 {
+                    f.write_str("\n         ")?;
+                }
+                f.write_str(line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[unstable(feature = "error_reporter", issue = "0")]
+impl<E: Error> From<E> for Report<E> {
+    fn from(error: E) -> Report<E> {
+        Report::new(error)
+    }
+}
+
+#[unstable(feature = "error_reporter", issue = "0")]
+impl<E: Error> Display for Report<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.pretty {
+            self.fmt_multi(f)
+        } else {
+            self.fmt_single(f)
+        }
+    }
+}
+
+#[unstable(feature = "error_reporter", issue = "0")]
+impl<E: Error> Debug for Report<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Error;
+    use prelude::*;
+    use super::{Error, Report};
     use fmt;
+    use io;
 
     #[derive(Debug, PartialEq)]
     struct A;
@@ -559,4 +771,42 @@ mod tests {
             Err(e) => assert_eq!(*e.downcast::<A>().unwrap(), A),
         }
     }
+
+    #[derive(Debug)]
+    struct Wrapper(A);
+
+    impl fmt::Display for Wrapper {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "wrapper")
+        }
+    }
+
+    impl Error for Wrapper {
+        fn description(&self) -> &str { "wrapper-desc" }
+        fn cause(&self) -> Option<&Error> { Some(&self.0) }
+    }
+
+    #[test]
+    fn chain_and_report() {
+        let w = Wrapper(A);
+        assert_eq!((&w as &(Error + 'static)).chain().count(), 2);
+
+        assert_eq!(Report::new(Wrapper(A)).to_string(), "wrapper: A");
+        assert_eq!(Report::new(Wrapper(A)).pretty(true).to_string(),
+                   "wrapper\n\nCaused by:\n      0: A");
+
+        // An io::Error exposes its payload as the source, but reports show
+        // the payload's message once; context goes in front of it.
+        let err = io::Error::new(io::ErrorKind::Other, Wrapper(A));
+        assert_eq!((&err as &(Error + 'static)).chain().count(), 3);
+        assert_eq!(err.source().unwrap().description(), "wrapper-desc");
+        assert_eq!(Report::new(err).to_string(), "wrapper: A");
+
+        let err = io::Error::new(io::ErrorKind::Other, "foo").with_context("ctx");
+        assert_eq!(Report::new(err).to_string(), "ctx: foo");
+
+        let err = io::Error::new(io::ErrorKind::Other, Wrapper(A)).with_context("ctx");
+        assert_eq!(Report::new(err).pretty(true).to_string(),
+                   "ctx: wrapper\n\nCaused by:\n      0: A");
+    }
 }
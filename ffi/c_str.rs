@@ -771,8 +771,8 @@ impl fmt::Display for NulError {
 
 impl<STD: Std + ?Sized> From<NulError> for io::Error<STD> {
     fn from(_: NulError) -> io::Error<STD> {
-        io::Error::new(io::ErrorKind::InvalidInput,
-                       "data provided contains a nul byte")
+        const_error!(io::ErrorKind::InvalidInput,
+                     "data provided contains a nul byte")
     }
 }
 
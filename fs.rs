@@ -27,14 +27,23 @@ use traits::{
     FileType as FileTypeT,
     DirBuilder as DirBuilderT,
     DirEntry as DirEntryT,
+    SystemTime as SystemTimeT,
 };
 
+use alloc::arc::Arc;
+use cmp;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use error;
 use fmt;
-use ffi::OsString;
-use io::{self, SeekFrom, Seek, Read, Initializer, Write};
+use ffi::{OsStr, OsString};
+use io::{self, SeekFrom, Seek, Read, Write, IoSlice, IoSliceMut, BorrowedCursor};
 use path::{Path, PathBuf};
 use sys_common::{AsInnerMut, FromInner, AsInner, IntoInner};
+use sys_common::fs::PositionalLock;
+use sys_common::thread_pool::{spawn_blocking, Blocking};
+use task::{Context, Future, Poll};
 use time::SystemTime;
+use vec;
 
 /// A reference to an open file on the filesystem.
 ///
@@ -99,6 +108,7 @@ use time::SystemTime;
 // #[stable(feature = "rust1", since = "1.0.0")]
 pub struct File<STD: Std> {
     inner: STD::File,
+    positional: PositionalLock<STD>,
 }
 
 /// Metadata information about a file.
@@ -513,7 +523,8 @@ impl<STD: Std> File<STD> {
     // #[stable(feature = "file_try_clone", since = "1.9.0")]
     pub fn try_clone(&self) -> io::Result<File<STD>, STD> {
         Ok(File {
-            inner: self.inner.duplicate()?
+            inner: self.inner.duplicate()?,
+            positional: PositionalLock::new(),
         })
     }
 
@@ -550,6 +561,195 @@ impl<STD: Std> File<STD> {
     pub fn set_permissions(&self, perm: Permissions<STD>) -> io::Result<(), STD> {
         self.inner.set_permissions(perm.0)
     }
+
+    /// Moves this file in or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, reads and writes that can't complete right away
+    /// fail with `ErrorKind::WouldBlock` instead of waiting. This is meant
+    /// for pipes and similar handles watched by an [`io::poll::Poller`];
+    /// regular files are always ready.
+    ///
+    /// Platforms without non-blocking I/O return an error when asked to
+    /// enable it.
+    ///
+    /// [`io::poll::Poller`]: ../io/poll/struct.Poller.html
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<(), STD> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Acquires a shared lock on the file, blocking until it's available.
+    ///
+    /// Any number of handles may hold a shared lock at once, but none while
+    /// another handle holds an exclusive lock. Locks are advisory: they only
+    /// keep out other users of the locking methods, not plain reads and
+    /// writes. A lock is released by [`unlock`] or when the last handle to
+    /// it, including ones made by [`try_clone`], is closed.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This corresponds to `flock` or open file description locks on Linux.
+    /// Platforms without file locking return an `ErrorKind::Unsupported`
+    /// error, unless the backend sets `traits::File::NOOP_LOCKING`, in which
+    /// case locking always succeeds without doing anything.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use fs::File;
+    ///
+    /// # fn foo() -> ::std::io::Result<()> {
+    /// let f = File::open("cache/index")?;
+    /// f.lock_shared()?;
+    /// // read the index
+    /// f.unlock()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`unlock`]: #method.unlock
+    /// [`try_clone`]: #method.try_clone
+    pub fn lock_shared(&self) -> io::Result<(), STD> {
+        self.inner.lock_shared()
+    }
+
+    /// Acquires an exclusive lock on the file, blocking until it's
+    /// available.
+    ///
+    /// No other handle may hold any lock on the file at the same time. See
+    /// [`lock_shared`] for how locks behave.
+    ///
+    /// [`lock_shared`]: #method.lock_shared
+    pub fn lock_exclusive(&self) -> io::Result<(), STD> {
+        self.inner.lock_exclusive()
+    }
+
+    /// Tries to acquire a shared lock on the file without blocking.
+    ///
+    /// Returns `Ok(false)` if another handle holds an exclusive lock.
+    pub fn try_lock_shared(&self) -> io::Result<bool, STD> {
+        self.inner.try_lock_shared()
+    }
+
+    /// Tries to acquire an exclusive lock on the file without blocking.
+    ///
+    /// Returns `Ok(false)` if another handle holds any lock.
+    pub fn try_lock_exclusive(&self) -> io::Result<bool, STD> {
+        self.inner.try_lock_exclusive()
+    }
+
+    /// Releases the lock held through this handle, if any.
+    pub fn unlock(&self) -> io::Result<(), STD> {
+        self.inner.unlock()
+    }
+
+    /// Reads a number of bytes starting from a given offset.
+    ///
+    /// Returns the number of bytes read. The offset is relative to the start
+    /// of the file and thus independent from the current cursor, which is
+    /// not changed by this function. This makes it suitable for concurrent
+    /// readers sharing a `&File`.
+    ///
+    /// Note that similar to [`read`], it is not an error to return with a
+    /// short read.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This corresponds to `pread` on platforms that support it. Platforms
+    /// without native support emulate it by seeking. Emulated positional
+    /// calls on the same `File` are serialized with each other, but the
+    /// cursor does move temporarily, so mixing them with concurrent `read`,
+    /// `write` or `seek` calls, or with positional calls through a
+    /// [`try_clone`]d handle sharing the cursor, is not safe.
+    ///
+    /// [`read`]: ../io/trait.Read.html#tymethod.read
+    /// [`try_clone`]: #method.try_clone
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize, STD> {
+        let _guard = self.positional.lock();
+        self.inner.read_at(buf, offset)
+    }
+
+    /// Writes a number of bytes starting from a given offset.
+    ///
+    /// Returns the number of bytes written. The offset is relative to the
+    /// start of the file and thus independent from the current cursor, which
+    /// is not changed by this function.
+    ///
+    /// Note that similar to [`write`], it is not an error to return a short
+    /// write.
+    ///
+    /// # Platform-specific behavior
+    ///
+    /// This corresponds to `pwrite` on platforms that support it. Platforms
+    /// without native support emulate it by seeking, with the same caveats
+    /// as [`read_at`].
+    ///
+    /// [`write`]: ../io/trait.Write.html#tymethod.write
+    /// [`read_at`]: #method.read_at
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize, STD> {
+        let _guard = self.positional.lock();
+        self.inner.write_at(buf, offset)
+    }
+
+    /// Reads the exact number of bytes required to fill `buf` from the given
+    /// offset.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters an "end of file" before completely
+    /// filling the buffer, it returns an error of the kind
+    /// [`ErrorKind::UnexpectedEof`]. The contents of `buf` are unspecified in
+    /// this case.
+    ///
+    /// Errors of the kind [`ErrorKind::Interrupted`] are retried.
+    ///
+    /// [`ErrorKind::UnexpectedEof`]: ../io/enum.ErrorKind.html#variant.UnexpectedEof
+    /// [`ErrorKind::Interrupted`]: ../io/enum.ErrorKind.html#variant.Interrupted
+    pub fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<(), STD> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !buf.is_empty() {
+            Err(const_error!(io::ErrorKind::UnexpectedEof,
+                             "failed to fill whole buffer"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Attempts to write an entire buffer starting from a given offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`ErrorKind::WriteZero`] if the file
+    /// stops accepting data. Errors of the kind [`ErrorKind::Interrupted`]
+    /// are retried.
+    ///
+    /// [`ErrorKind::WriteZero`]: ../io/enum.ErrorKind.html#variant.WriteZero
+    /// [`ErrorKind::Interrupted`]: ../io/enum.ErrorKind.html#variant.Interrupted
+    pub fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> io::Result<(), STD> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => return Err(const_error!(io::ErrorKind::WriteZero,
+                                                 "failed to write whole buffer")),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<STD: Std> AsInner<STD::File> for File<STD> {
@@ -557,7 +757,7 @@ impl<STD: Std> AsInner<STD::File> for File<STD> {
 }
 impl<STD: Std> FromInner<STD::File> for File<STD> {
     fn from_inner(f: STD::File) -> File<STD> {
-        File { inner: f }
+        File { inner: f, positional: PositionalLock::new() }
     }
 }
 impl<STD: Std> IntoInner<STD::File> for File<STD> {
@@ -579,9 +779,12 @@ impl<STD: Std> Read<STD> for File<STD> {
         self.inner.read(buf)
     }
 
-    #[inline]
-    unsafe fn initializer(&self) -> Initializer {
-        Initializer::nop()
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize, STD> {
+        self.inner.read_vectored(bufs)
+    }
+
+    fn read_buf(&mut self, cursor: BorrowedCursor) -> io::Result<(), STD> {
+        self.inner.read_buf(cursor)
     }
 }
 // #[stable(feature = "rust1", since = "1.0.0")]
@@ -589,6 +792,10 @@ impl<STD: Std> Write<STD> for File<STD> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize, STD> {
         self.inner.write(buf)
     }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize, STD> {
+        self.inner.write_vectored(bufs)
+    }
     fn flush(&mut self) -> io::Result<(), STD> { self.inner.flush() }
 }
 // #[stable(feature = "rust1", since = "1.0.0")]
@@ -603,9 +810,12 @@ impl<'a, STD: Std> Read<STD> for &'a File<STD> {
         self.inner.read(buf)
     }
 
-    #[inline]
-    unsafe fn initializer(&self) -> Initializer {
-        Initializer::nop()
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize, STD> {
+        self.inner.read_vectored(bufs)
+    }
+
+    fn read_buf(&mut self, cursor: BorrowedCursor) -> io::Result<(), STD> {
+        self.inner.read_buf(cursor)
     }
 }
 // #[stable(feature = "rust1", since = "1.0.0")]
@@ -613,6 +823,10 @@ impl<'a, STD: Std> Write<STD> for &'a File<STD> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize, STD> {
         self.inner.write(buf)
     }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize, STD> {
+        self.inner.write_vectored(bufs)
+    }
     fn flush(&mut self) -> io::Result<(), STD> { self.inner.flush() }
 }
 // #[stable(feature = "rust1", since = "1.0.0")]
@@ -622,6 +836,455 @@ impl<'a, STD: Std> Seek<STD> for &'a File<STD> {
     }
 }
 
+/// An asynchronous wrapper around a [`File`].
+///
+/// Operations run on a small pool of blocking threads, so they never block
+/// the task polling them. Writes are buffered and complete in the
+/// background; flush the file to wait for them and to see their errors. On
+/// platforms without threads every operation runs inline.
+///
+/// [`File`]: struct.File.html
+pub struct AsyncFile<STD: Std> {
+    inner: Arc<File<STD>>,
+    state: AsyncState<STD>,
+}
+
+// The most bytes moved to or from the blocking pool in one operation.
+const MAX_ASYNC_BUF: usize = 64 * 1024;
+
+enum AsyncState<STD: Std> {
+    Idle(Option<AsyncBuf>),
+    Busy(Blocking<(AsyncOp<STD>, AsyncBuf)>),
+}
+
+enum AsyncOp<STD: Std> {
+    Read(io::Result<usize, STD>),
+    Write(io::Result<(), STD>),
+    Seek(io::Result<u64, STD>),
+}
+
+// Data read ahead of the caller lives in `data[pos..]`.
+struct AsyncBuf {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl AsyncBuf {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn copy_to(&mut self, dst: &mut [u8]) -> usize {
+        let n = cmp::min(self.remaining(), dst.len());
+        dst[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        n
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+        self.pos = 0;
+    }
+}
+
+impl<STD: Std> AsyncFile<STD> where STD::File: Send + Sync {
+    /// Opens a file in read-only mode, like [`File::open`].
+    ///
+    /// [`File::open`]: struct.File.html#method.open
+    pub fn open<P: AsRef<Path<STD>>>(path: P) -> Blocking<io::Result<AsyncFile<STD>, STD>>
+        where PathBuf<STD>: Send
+    {
+        let path = path.as_ref().to_path_buf();
+        spawn_blocking::<STD, _, _>(move || File::open(path).map(AsyncFile::new))
+    }
+
+    /// Opens a file in write-only mode, like [`File::create`].
+    ///
+    /// [`File::create`]: struct.File.html#method.create
+    pub fn create<P: AsRef<Path<STD>>>(path: P) -> Blocking<io::Result<AsyncFile<STD>, STD>>
+        where PathBuf<STD>: Send
+    {
+        let path = path.as_ref().to_path_buf();
+        spawn_blocking::<STD, _, _>(move || File::create(path).map(AsyncFile::new))
+    }
+
+    /// Wraps an already open file.
+    pub fn new(file: File<STD>) -> AsyncFile<STD> {
+        AsyncFile {
+            inner: Arc::new(file),
+            state: AsyncState::Idle(Some(AsyncBuf { data: Vec::new(), pos: 0 })),
+        }
+    }
+
+    fn spawn<F>(&mut self, mut buf: AsyncBuf, f: F)
+        where F: FnOnce(&File<STD>, &mut AsyncBuf) -> AsyncOp<STD> + Send + 'static
+    {
+        let file = self.inner.clone();
+        self.state = AsyncState::Busy(spawn_blocking::<STD, _, _>(move || {
+            let op = f(&file, &mut buf);
+            (op, buf)
+        }));
+    }
+
+    // Waits for the operation in flight, if any, and returns its outcome.
+    fn poll_complete(&mut self, cx: &mut Context) -> Poll<Option<AsyncOp<STD>>> {
+        let (op, buf) = match self.state {
+            AsyncState::Idle(_) => return Poll::Ready(None),
+            AsyncState::Busy(ref mut job) => match job.poll(cx) {
+                Poll::Ready(ret) => ret,
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+        self.state = AsyncState::Idle(Some(buf));
+        Poll::Ready(Some(op))
+    }
+
+    fn take_buf(&mut self) -> AsyncBuf {
+        match self.state {
+            AsyncState::Idle(ref mut buf) => buf.take().expect("buffer already taken"),
+            AsyncState::Busy(_) => panic!("operation still in flight"),
+        }
+    }
+}
+
+impl<STD: Std> io::AsyncRead<STD> for AsyncFile<STD> where STD::File: Send + Sync {
+    fn poll_read(&mut self, cx: &mut Context, dst: &mut [u8])
+                 -> Poll<io::Result<usize, STD>> {
+        let this = self;
+        loop {
+            match this.poll_complete(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(AsyncOp::Read(Ok(_)))) => {
+                    if let AsyncState::Idle(Some(ref mut buf)) = this.state {
+                        return Poll::Ready(Ok(buf.copy_to(dst)));
+                    }
+                }
+                Poll::Ready(Some(AsyncOp::Read(Err(e)))) |
+                Poll::Ready(Some(AsyncOp::Write(Err(e)))) => return Poll::Ready(Err(e)),
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => {
+                    let mut buf = this.take_buf();
+                    if buf.remaining() != 0 {
+                        let n = buf.copy_to(dst);
+                        this.state = AsyncState::Idle(Some(buf));
+                        return Poll::Ready(Ok(n));
+                    }
+
+                    let len = cmp::min(dst.len(), MAX_ASYNC_BUF);
+                    buf.clear();
+                    buf.data.resize(len, 0);
+                    this.spawn(buf, |file, buf| {
+                        let mut file = file;
+                        let ret = file.read(&mut buf.data);
+                        buf.data.truncate(*ret.as_ref().unwrap_or(&0));
+                        AsyncOp::Read(ret)
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<STD: Std> io::AsyncWrite<STD> for AsyncFile<STD> where STD::File: Send + Sync {
+    fn poll_write(&mut self, cx: &mut Context, src: &[u8])
+                  -> Poll<io::Result<usize, STD>> {
+        let this = self;
+        loop {
+            match this.poll_complete(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(AsyncOp::Write(Err(e)))) => return Poll::Ready(Err(e)),
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => {
+                    let mut buf = this.take_buf();
+
+                    // Data read ahead moved the cursor past what the caller
+                    // has seen, so move it back before writing.
+                    let rewind = buf.remaining() as i64;
+                    let n = cmp::min(src.len(), MAX_ASYNC_BUF);
+                    buf.clear();
+                    buf.data.extend_from_slice(&src[..n]);
+                    this.spawn(buf, move |file, buf| {
+                        let mut file = file;
+                        let mut ret = Ok(());
+                        if rewind != 0 {
+                            ret = file.seek(SeekFrom::Current(-rewind)).map(|_| ());
+                        }
+                        let ret = ret.and_then(|_| file.write_all(&buf.data));
+                        buf.clear();
+                        AsyncOp::Write(ret)
+                    });
+                    return Poll::Ready(Ok(n));
+                }
+            }
+        }
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<io::Result<(), STD>> {
+        let this = self;
+        loop {
+            match this.poll_complete(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(AsyncOp::Write(Err(e)))) => return Poll::Ready(Err(e)),
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+impl<STD: Std> io::AsyncSeek<STD> for AsyncFile<STD> where STD::File: Send + Sync {
+    fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom)
+                 -> Poll<io::Result<u64, STD>> {
+        let this = self;
+        loop {
+            match this.poll_complete(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(AsyncOp::Seek(ret))) => return Poll::Ready(ret),
+                Poll::Ready(Some(AsyncOp::Write(Err(e)))) => return Poll::Ready(Err(e)),
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => {
+                    let mut buf = this.take_buf();
+                    let mut pos = pos;
+                    if let SeekFrom::Current(ref mut offset) = pos {
+                        *offset -= buf.remaining() as i64;
+                    }
+                    buf.clear();
+                    this.spawn(buf, move |file, _| {
+                        let mut file = file;
+                        AsyncOp::Seek(file.seek(pos))
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<STD: Std> fmt::Debug for AsyncFile<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let busy = match self.state {
+            AsyncState::Idle(_) => false,
+            AsyncState::Busy(_) => true,
+        };
+        f.debug_struct("AsyncFile")
+            .field("inner", &self.inner)
+            .field("busy", &busy)
+            .finish()
+    }
+}
+
+/// A queue of filesystem operations submitted together.
+///
+/// Operations are queued with the builder-like methods, each of which
+/// returns the index of its result, and run by [`submit`]. Backends with a
+/// submission queue (such as io_uring) hand the whole batch to the kernel at
+/// once through `Std::submit_batch`; elsewhere the operations simply run one
+/// after the other.
+///
+/// Operations in a batch may run in any order and concurrently with each
+/// other, so they must not depend on each other's results.
+///
+/// [`submit`]: #method.submit
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::{Batch, BatchResult};
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let mut batch = Batch::new();
+/// let a = batch.metadata("a.txt".as_ref());
+/// let b = batch.metadata("b.txt".as_ref());
+/// let results = batch.submit()?;
+/// if let Ok(BatchResult::Metadata(ref meta)) = results[a] {
+///     println!("a.txt is {} bytes", meta.len());
+/// }
+/// # let _ = b;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Batch<'a, STD: Std + 'a> {
+    ops: Vec<BatchOp<'a, STD>>,
+}
+
+/// An operation queued in a [`Batch`].
+///
+/// [`Batch`]: struct.Batch.html
+pub enum BatchOp<'a, STD: Std + 'a> {
+    /// Opens `path` with `options`.
+    Open { path: &'a Path<STD>, options: &'a OpenOptions<STD> },
+    /// Queries the metadata of `path`, following symlinks.
+    Metadata { path: &'a Path<STD> },
+    /// Reads into `buf` from `offset` in `file`.
+    ReadAt { file: &'a File<STD>, buf: &'a mut [u8], offset: u64 },
+    /// Writes `buf` at `offset` in `file`.
+    WriteAt { file: &'a File<STD>, buf: &'a [u8], offset: u64 },
+    /// Syncs `file` to disk.
+    SyncAll { file: &'a File<STD> },
+    /// Closes `file`.
+    Close { file: File<STD> },
+}
+
+/// The outcome of a successful [`BatchOp`].
+///
+/// [`BatchOp`]: enum.BatchOp.html
+#[derive(Debug)]
+pub enum BatchResult<STD: Std> {
+    /// The file opened by `BatchOp::Open`.
+    Opened(File<STD>),
+    /// The metadata queried by `BatchOp::Metadata`.
+    Metadata(Metadata<STD>),
+    /// The number of bytes read by `BatchOp::ReadAt`.
+    Read(usize),
+    /// The number of bytes written by `BatchOp::WriteAt`.
+    Written(usize),
+    /// `BatchOp::SyncAll` completed.
+    Synced,
+    /// `BatchOp::Close` completed.
+    Closed,
+}
+
+impl<'a, STD: Std> BatchOp<'a, STD> {
+    /// Runs the operation right away through the regular blocking calls.
+    ///
+    /// This is what backends without a submission queue do for every
+    /// operation, and what others can do for operations they can't queue.
+    pub fn execute(self) -> io::Result<BatchResult<STD>, STD> {
+        match self {
+            BatchOp::Open { path, options } => options.open(path).map(BatchResult::Opened),
+            BatchOp::Metadata { path } => metadata(path).map(BatchResult::Metadata),
+            BatchOp::ReadAt { file, buf, offset } => {
+                file.read_at(buf, offset).map(BatchResult::Read)
+            }
+            BatchOp::WriteAt { file, buf, offset } => {
+                file.write_at(buf, offset).map(BatchResult::Written)
+            }
+            BatchOp::SyncAll { file } => file.sync_all().map(|()| BatchResult::Synced),
+            BatchOp::Close { file } => {
+                drop(file);
+                Ok(BatchResult::Closed)
+            }
+        }
+    }
+}
+
+impl<'a, STD: Std> fmt::Debug for BatchOp<'a, STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BatchOp::Open { path, .. } => {
+                f.debug_struct("Open").field("path", &path).finish()
+            }
+            BatchOp::Metadata { path } => {
+                f.debug_struct("Metadata").field("path", &path).finish()
+            }
+            BatchOp::ReadAt { file, ref buf, offset } => {
+                f.debug_struct("ReadAt")
+                    .field("file", file)
+                    .field("len", &buf.len())
+                    .field("offset", &offset)
+                    .finish()
+            }
+            BatchOp::WriteAt { file, buf, offset } => {
+                f.debug_struct("WriteAt")
+                    .field("file", file)
+                    .field("len", &buf.len())
+                    .field("offset", &offset)
+                    .finish()
+            }
+            BatchOp::SyncAll { file } => f.debug_struct("SyncAll").field("file", file).finish(),
+            BatchOp::Close { ref file } => f.debug_struct("Close").field("file", file).finish(),
+        }
+    }
+}
+
+impl<'a, STD: Std> Batch<'a, STD> {
+    /// Creates an empty batch.
+    pub fn new() -> Batch<'a, STD> {
+        Batch { ops: Vec::new() }
+    }
+
+    /// Creates an empty batch with room for `capacity` operations.
+    pub fn with_capacity(capacity: usize) -> Batch<'a, STD> {
+        Batch { ops: Vec::with_capacity(capacity) }
+    }
+
+    /// Queues an operation, returning the index of its result.
+    pub fn push(&mut self, op: BatchOp<'a, STD>) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    /// Queues opening `path` with `options`, like [`OpenOptions::open`].
+    ///
+    /// [`OpenOptions::open`]: struct.OpenOptions.html#method.open
+    pub fn open(&mut self, path: &'a Path<STD>, options: &'a OpenOptions<STD>) -> usize {
+        self.push(BatchOp::Open { path: path, options: options })
+    }
+
+    /// Queues querying the metadata of `path`, like [`metadata`].
+    ///
+    /// [`metadata`]: fn.metadata.html
+    pub fn metadata(&mut self, path: &'a Path<STD>) -> usize {
+        self.push(BatchOp::Metadata { path: path })
+    }
+
+    /// Queues a read at `offset`, like [`File::read_at`].
+    ///
+    /// [`File::read_at`]: struct.File.html#method.read_at
+    pub fn read_at(&mut self, file: &'a File<STD>, buf: &'a mut [u8], offset: u64) -> usize {
+        self.push(BatchOp::ReadAt { file: file, buf: buf, offset: offset })
+    }
+
+    /// Queues a write at `offset`, like [`File::write_at`].
+    ///
+    /// [`File::write_at`]: struct.File.html#method.write_at
+    pub fn write_at(&mut self, file: &'a File<STD>, buf: &'a [u8], offset: u64) -> usize {
+        self.push(BatchOp::WriteAt { file: file, buf: buf, offset: offset })
+    }
+
+    /// Queues syncing `file` to disk, like [`File::sync_all`].
+    ///
+    /// [`File::sync_all`]: struct.File.html#method.sync_all
+    pub fn sync_all(&mut self, file: &'a File<STD>) -> usize {
+        self.push(BatchOp::SyncAll { file: file })
+    }
+
+    /// Queues closing `file`.
+    pub fn close(&mut self, file: File<STD>) -> usize {
+        self.push(BatchOp::Close { file: file })
+    }
+
+    /// Returns the number of queued operations.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if no operations are queued.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Runs all queued operations and returns their results, in the order
+    /// they were queued.
+    ///
+    /// # Errors
+    ///
+    /// The outer error means the batch couldn't be submitted at all, in
+    /// which case none of the operations ran. Each operation can still fail
+    /// on its own.
+    pub fn submit(self) -> io::Result<Vec<io::Result<BatchResult<STD>, STD>>, STD> {
+        if self.ops.is_empty() {
+            return Ok(Vec::new());
+        }
+        STD::submit_batch(self.ops)
+    }
+}
+
+impl<'a, STD: Std> fmt::Debug for Batch<'a, STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.ops.iter()).finish()
+    }
+}
+
 impl<STD: Std> OpenOptions<STD> {
     /// Creates a blank new set of options ready for configuration.
     ///
@@ -848,7 +1511,7 @@ impl<STD: Std> OpenOptions<STD> {
 
     fn _open(&self, path: &Path<STD>) -> io::Result<File<STD>, STD> {
         let inner = STD::File::open(path, &self.0)?;
-        Ok(File { inner: inner })
+        Ok(File { inner: inner, positional: PositionalLock::new() })
     }
 }
 
@@ -1986,7 +2649,7 @@ impl<STD: Std> DirBuilder<STD> {
         }
         match path.parent() {
             Some(p) => try!(self.create_dir_all(p)),
-            None => return Err(io::Error::new(io::ErrorKind::Other, "failed to create whole tree")),
+            None => return Err(const_error!(io::ErrorKind::Other, "failed to create whole tree")),
         }
         match self.inner.mkdir(path) {
             Ok(()) => Ok(()),
@@ -2002,35 +2665,1574 @@ impl<STD: Std> AsInnerMut<STD::DirBuilder> for DirBuilder<STD> {
     }
 }
 
-#[cfg(all(test, not(any(target_os = "cloudabi", target_os = "emscripten"))))]
-mod tests {
-    use io::prelude::*;
+/// Returns an iterator over the directory tree rooted at `root`.
+///
+/// Shorthand for [`WalkDir::new`], see there for the available options.
+///
+/// [`WalkDir::new`]: struct.WalkDir.html#method.new
+pub fn walk_dir<P: AsRef<Path<STD>>, STD: Std>(root: P) -> WalkDir<STD> {
+    WalkDir::new(root)
+}
 
-    use fs::{self, File, OpenOptions};
-    use io::{ErrorKind, SeekFrom};
-    use path::Path;
-    use rand::{StdRng, Rng};
-    use str;
-    use sys_common::io::test::{TempDir, tmpdir};
-    use thread;
+/// A recursive iterator over the entries of a directory tree.
+///
+/// The root itself is the first entry, at depth 0, and directories are
+/// visited depth-first. An error reading one entry or directory is yielded
+/// in its place and the walk carries on with the rest of the tree.
+///
+/// The walk only relies on `Std::readdir` and `Std::lstat`, plus `Std::stat`
+/// when symlinks are followed. Symlink loops are detected by comparing
+/// [`FileAttr::file_id`] with the directories above; on platforms without
+/// file ids, canonical paths are compared instead.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::WalkDir;
+///
+/// for entry in WalkDir::new("src").sort_by_file_name() {
+///     match entry {
+///         Ok(entry) => println!("{}", entry.path().display()),
+///         Err(e) => eprintln!("{}", e),
+///     }
+/// }
+/// ```
+///
+/// Skipping hidden directories and everything below them:
+///
+/// ```ignore
+/// use fs::WalkDir;
+///
+/// let walk = WalkDir::new(".").filter_entry(|e| {
+///     !e.file_name().to_string_lossy().starts_with('.') || e.depth() == 0
+/// });
+/// for entry in walk.filter_map(|e| e.ok()) {
+///     println!("{}", entry.path().display());
+/// }
+/// ```
+///
+/// [`FileAttr::file_id`]: ../traits/trait.FileAttr.html#method.file_id
+pub struct WalkDir<STD: Std> {
+    root: Option<PathBuf<STD>>,
+    min_depth: usize,
+    max_depth: usize,
+    follow_links: bool,
+    contents_first: bool,
+    sort: bool,
+    filter: Option<Box<FnMut(&WalkEntry<STD>) -> bool>>,
+    stack: Vec<WalkFrame<STD>>,
+    deferred: Option<io::Error<STD>>,
+}
 
-    #[cfg(windows)]
-    use os::windows::fs::{symlink_dir, symlink_file};
-    #[cfg(windows)]
-    use sys::fs::symlink_junction;
-    #[cfg(unix)]
-    use os::unix::fs::symlink as symlink_dir;
-    #[cfg(unix)]
-    use os::unix::fs::symlink as symlink_file;
-    #[cfg(unix)]
-    use os::unix::fs::symlink as symlink_junction;
+struct WalkFrame<STD: Std> {
+    path: PathBuf<STD>,
+    entries: WalkEntries<STD>,
+    // The directory itself, held back until its contents have been yielded
+    // when walking contents-first.
+    dir: Option<WalkEntry<STD>>,
+    id: Option<WalkDirId<STD>>,
+}
 
-    macro_rules! check { ($e:expr) => (
-        match $e {
-            Ok(t) => t,
-            Err(e) => panic!("{} failed with: {}", stringify!($e), e),
-        }
-    ) }
+enum WalkEntries<STD: Std> {
+    Unsorted(STD::ReadDir),
+    Sorted(vec::IntoIter<io::Result<STD::DirEntry, STD>>),
+}
+
+impl<STD: Std> Iterator for WalkEntries<STD> {
+    type Item = io::Result<STD::DirEntry, STD>;
+
+    fn next(&mut self) -> Option<io::Result<STD::DirEntry, STD>> {
+        match *self {
+            WalkEntries::Unsorted(ref mut it) => it.next(),
+            WalkEntries::Sorted(ref mut it) => it.next(),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum WalkDirId<STD: Std> {
+    File((u64, u64)),
+    Path(PathBuf<STD>),
+}
+
+impl<STD: Std> WalkDir<STD> {
+    /// Creates a walk over the tree rooted at `root`.
+    ///
+    /// By default every entry is yielded, at any depth, in the order
+    /// `read_dir` returns them, directories before their contents, and
+    /// symlinks are not followed.
+    pub fn new<P: AsRef<Path<STD>>>(root: P) -> WalkDir<STD> {
+        WalkDir {
+            root: Some(root.as_ref().to_path_buf()),
+            min_depth: 0,
+            max_depth: usize::max_value(),
+            follow_links: false,
+            contents_first: false,
+            sort: false,
+            filter: None,
+            stack: Vec::new(),
+            deferred: None,
+        }
+    }
+
+    /// Only yields entries at least `depth` levels below the root.
+    ///
+    /// Shallower directories are still descended into.
+    pub fn min_depth(mut self, depth: usize) -> WalkDir<STD> {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Doesn't descend more than `depth` levels below the root.
+    ///
+    /// A depth of 0 yields only the root.
+    pub fn max_depth(mut self, depth: usize) -> WalkDir<STD> {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Follows symlinks, yielding the entries of the directories they point
+    /// to as if they were part of the tree.
+    ///
+    /// A symlink pointing back to one of its ancestors yields an
+    /// `ErrorKind::FilesystemLoop` error instead of being followed.
+    pub fn follow_links(mut self, follow: bool) -> WalkDir<STD> {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Yields directories after their contents instead of before them, as
+    /// needed to remove a tree.
+    pub fn contents_first(mut self, contents_first: bool) -> WalkDir<STD> {
+        self.contents_first = contents_first;
+        self
+    }
+
+    /// Yields the entries of each directory sorted by file name.
+    ///
+    /// This reads each directory completely before yielding its first
+    /// entry.
+    pub fn sort_by_file_name(mut self) -> WalkDir<STD> {
+        self.sort = true;
+        self
+    }
+
+    /// Only yields entries for which `predicate` returns `true`.
+    ///
+    /// A directory that is rejected is not descended into, so the whole
+    /// subtree below it is skipped. The predicate sees entries above
+    /// `min_depth` as well.
+    pub fn filter_entry<F>(mut self, predicate: F) -> WalkDir<STD>
+        where F: FnMut(&WalkEntry<STD>) -> bool + 'static
+    {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+
+    fn root_entry(&self, path: PathBuf<STD>) -> io::Result<WalkEntry<STD>, STD> {
+        let mut file_type = STD::lstat(&path)?.file_type();
+        let mut follow_link = false;
+        if file_type.is_symlink() && self.follow_links {
+            file_type = STD::stat(&path)?.file_type();
+            follow_link = true;
+        }
+        Ok(WalkEntry {
+            path: path,
+            depth: 0,
+            file_type: FileType(file_type),
+            follow_link: follow_link,
+        })
+    }
+
+    fn child_entry(&self, ent: STD::DirEntry, depth: usize) -> io::Result<WalkEntry<STD>, STD> {
+        let path = ent.path();
+        let mut file_type = match ent.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => return Err(walk_error(e, &path)),
+        };
+        let mut follow_link = false;
+        if file_type.is_symlink() && self.follow_links {
+            file_type = match STD::stat(&path) {
+                Ok(attr) => attr.file_type(),
+                Err(e) => return Err(walk_error(e, &path)),
+            };
+            follow_link = true;
+        }
+        Ok(WalkEntry {
+            path: path,
+            depth: depth,
+            file_type: FileType(file_type),
+            follow_link: follow_link,
+        })
+    }
+
+    fn dir_id(&self, path: &Path<STD>) -> io::Result<WalkDirId<STD>, STD> {
+        match STD::stat(path)?.file_id() {
+            Some(id) => Ok(WalkDirId::File(id)),
+            None => Ok(WalkDirId::Path(STD::canonicalize(path)?)),
+        }
+    }
+
+    fn read_dir(&self, path: &Path<STD>) -> io::Result<WalkEntries<STD>, STD> {
+        let entries = STD::readdir(path)?;
+        if !self.sort {
+            return Ok(WalkEntries::Unsorted(entries));
+        }
+
+        // Errors have no name and sort first.
+        let mut named: Vec<_> = entries.map(|ent| {
+            (ent.as_ref().ok().map(|ent| ent.file_name()), ent)
+        }).collect();
+        named.sort_by(|a, b| a.0.cmp(&b.0));
+        let sorted: Vec<_> = named.into_iter().map(|(_, ent)| ent).collect();
+        Ok(WalkEntries::Sorted(sorted.into_iter()))
+    }
+
+    // Descends into `entry` if needed and returns what to yield next, if
+    // anything.
+    fn push(&mut self, entry: WalkEntry<STD>) -> Option<io::Result<WalkEntry<STD>, STD>> {
+        if let Some(ref mut filter) = self.filter {
+            if !filter(&entry) {
+                return None;
+            }
+        }
+
+        if !entry.file_type.is_dir() || entry.depth >= self.max_depth {
+            return self.yielded(entry);
+        }
+
+        let id = if self.follow_links {
+            match self.dir_id(&entry.path) {
+                Ok(id) => Some(id),
+                Err(e) => return Some(Err(walk_error(e, &entry.path))),
+            }
+        } else {
+            None
+        };
+        if id.is_some() && self.stack.iter().any(|frame| frame.id == id) {
+            let err = const_error!(io::ErrorKind::FilesystemLoop,
+                                   "symlink points to one of its ancestors");
+            return Some(Err(walk_error(err, &entry.path)));
+        }
+
+        match self.read_dir(&entry.path) {
+            Ok(entries) => {
+                let path = entry.path.clone();
+                let (dir, ret) = if self.contents_first {
+                    (Some(entry), None)
+                } else {
+                    (None, self.yielded(entry))
+                };
+                self.stack.push(WalkFrame { path: path, entries: entries, dir: dir, id: id });
+                ret
+            }
+            Err(e) => {
+                // The directory exists even if it can't be read, so yield it
+                // first and report the error right after.
+                self.deferred = Some(walk_error(e, &entry.path));
+                self.yielded(entry)
+            }
+        }
+    }
+
+    fn yielded(&self, entry: WalkEntry<STD>) -> Option<io::Result<WalkEntry<STD>, STD>> {
+        if entry.depth >= self.min_depth {
+            Some(Ok(entry))
+        } else {
+            None
+        }
+    }
+}
+
+fn walk_error<STD: Std>(err: io::Error<STD>, path: &Path<STD>) -> io::Error<STD> {
+    err.with_context(fmt::format(format_args!("failed to walk `{}`", path.display())))
+}
+
+impl<STD: Std> Iterator for WalkDir<STD> {
+    type Item = io::Result<WalkEntry<STD>, STD>;
+
+    fn next(&mut self) -> Option<io::Result<WalkEntry<STD>, STD>> {
+        if let Some(root) = self.root.take() {
+            let entry = match self.root_entry(root.clone()) {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(walk_error(e, &root))),
+            };
+            if let Some(ret) = self.push(entry) {
+                return Some(ret);
+            }
+        }
+
+        loop {
+            // An unreadable directory's error, reported after the directory
+            // itself or in its place if that wasn't yielded.
+            if let Some(err) = self.deferred.take() {
+                return Some(Err(err));
+            }
+
+            let next = match self.stack.last_mut() {
+                Some(frame) => match frame.entries.next() {
+                    Some(Err(e)) => Some(Err(walk_error(e, &frame.path))),
+                    next => next,
+                },
+                None => return None,
+            };
+            match next {
+                Some(Ok(ent)) => {
+                    let depth = self.stack.len();
+                    let ret = match self.child_entry(ent, depth) {
+                        Ok(entry) => self.push(entry),
+                        Err(e) => Some(Err(e)),
+                    };
+                    if ret.is_some() {
+                        return ret;
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    let frame = self.stack.pop().unwrap();
+                    if let Some(dir) = frame.dir {
+                        if let Some(ret) = self.yielded(dir) {
+                            return Some(ret);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<STD: Std> fmt::Debug for WalkDir<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WalkDir")
+            .field("min_depth", &self.min_depth)
+            .field("max_depth", &self.max_depth)
+            .field("follow_links", &self.follow_links)
+            .field("contents_first", &self.contents_first)
+            .field("sort", &self.sort)
+            .field("depth", &self.stack.len())
+            .finish()
+    }
+}
+
+/// An entry yielded by [`WalkDir`].
+///
+/// [`WalkDir`]: struct.WalkDir.html
+pub struct WalkEntry<STD: Std> {
+    path: PathBuf<STD>,
+    depth: usize,
+    file_type: FileType<STD>,
+    follow_link: bool,
+}
+
+impl<STD: Std> WalkEntry<STD> {
+    /// The full path of the entry, starting with the root of the walk.
+    pub fn path(&self) -> &Path<STD> {
+        &self.path
+    }
+
+    /// Consumes the entry, returning its path.
+    pub fn into_path(self) -> PathBuf<STD> {
+        self.path
+    }
+
+    /// The number of levels below the root, which is at depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The file name of the entry.
+    ///
+    /// For the root this is the root path itself when it has no file name,
+    /// as for `/` or `..`.
+    pub fn file_name(&self) -> &OsStr<STD> {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// The file type of the entry, or of the symlink's target if it was
+    /// followed.
+    pub fn file_type(&self) -> &FileType<STD> {
+        &self.file_type
+    }
+
+    /// Returns `true` if the entry is a symlink that was followed.
+    pub fn path_is_symlink(&self) -> bool {
+        self.follow_link
+    }
+
+    /// Queries the metadata of the entry, or of the symlink's target if it
+    /// was followed.
+    pub fn metadata(&self) -> io::Result<Metadata<STD>, STD> {
+        let attr = if self.follow_link {
+            STD::stat(&self.path)?
+        } else {
+            STD::lstat(&self.path)?
+        };
+        Ok(Metadata(attr))
+    }
+}
+
+impl<STD: Std> fmt::Debug for WalkEntry<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WalkEntry")
+            .field("path", &self.path)
+            .field("depth", &self.depth)
+            .field("file_type", &self.file_type)
+            .finish()
+    }
+}
+
+/// Options for [`copy_dir_all`].
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::{self, CopyOptions};
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let mut options = CopyOptions::new();
+/// options.overwrite(true).preserve_timestamps(true).progress(|p| {
+///     println!("{} files, {} bytes", p.files_copied(), p.bytes_copied());
+/// });
+/// fs::copy_dir_all("assets", "/srv/www/assets", &options)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`copy_dir_all`]: fn.copy_dir_all.html
+pub struct CopyOptions {
+    overwrite: bool,
+    preserve_permissions: bool,
+    preserve_timestamps: bool,
+    progress: Option<Box<Fn(&CopyProgress)>>,
+}
+
+impl CopyOptions {
+    /// Creates options that refuse to overwrite anything and don't carry
+    /// over permissions or timestamps.
+    pub fn new() -> CopyOptions {
+        CopyOptions {
+            overwrite: false,
+            preserve_permissions: false,
+            preserve_timestamps: false,
+            progress: None,
+        }
+    }
+
+    /// Replaces existing files and symlinks at the destination.
+    ///
+    /// Without this, copying onto an existing file or symlink fails with
+    /// `ErrorKind::AlreadyExists`. Existing directories are always merged
+    /// into.
+    pub fn overwrite(&mut self, overwrite: bool) -> &mut CopyOptions {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Copies the permissions of files and directories.
+    ///
+    /// Between two different backends only the read-only flag is carried
+    /// over, as it's the only part of `Permissions` every platform has.
+    pub fn preserve_permissions(&mut self, preserve: bool) -> &mut CopyOptions {
+        self.preserve_permissions = preserve;
+        self
+    }
+
+    /// Copies the access and modification times of files.
+    ///
+    /// Fails with `ErrorKind::Unsupported` if the destination can't set
+    /// file times.
+    pub fn preserve_timestamps(&mut self, preserve: bool) -> &mut CopyOptions {
+        self.preserve_timestamps = preserve;
+        self
+    }
+
+    /// Calls `progress` after each entry is copied.
+    pub fn progress<F: Fn(&CopyProgress) + 'static>(&mut self, progress: F) -> &mut CopyOptions {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+impl fmt::Debug for CopyOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyOptions")
+            .field("overwrite", &self.overwrite)
+            .field("preserve_permissions", &self.preserve_permissions)
+            .field("preserve_timestamps", &self.preserve_timestamps)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// The progress of [`copy_dir_all`], as reported to the callback set with
+/// [`CopyOptions::progress`].
+///
+/// [`copy_dir_all`]: fn.copy_dir_all.html
+/// [`CopyOptions::progress`]: struct.CopyOptions.html#method.progress
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CopyProgress {
+    bytes_copied: u64,
+    files_copied: u64,
+    dirs_created: u64,
+    symlinks_created: u64,
+}
+
+impl CopyProgress {
+    /// The number of bytes of file contents copied so far.
+    pub fn bytes_copied(&self) -> u64 { self.bytes_copied }
+
+    /// The number of files copied so far.
+    pub fn files_copied(&self) -> u64 { self.files_copied }
+
+    /// The number of directories created so far.
+    pub fn dirs_created(&self) -> u64 { self.dirs_created }
+
+    /// The number of symlinks created so far.
+    pub fn symlinks_created(&self) -> u64 { self.symlinks_created }
+}
+
+/// Recursively copies the directory `from` to `to`, returning the number of
+/// bytes of file contents copied.
+///
+/// Directories are created as needed, files are copied and symlinks are
+/// recreated pointing at the same target, never followed. `from` and `to`
+/// may live on different backends, such as an archive-backed `A` and the
+/// host's `B`; file names and symlink targets are carried over byte for
+/// byte.
+///
+/// The copy stops at the first error, leaving what has been copied so far
+/// in place.
+///
+/// # Errors
+///
+/// Besides the errors of the underlying operations, this fails with
+/// `ErrorKind::AlreadyExists` if a file or symlink exists at the
+/// destination and [`CopyOptions::overwrite`] isn't set, and with
+/// `ErrorKind::InvalidInput` if `to` is `from` or inside it on the same
+/// backend. Errors name the path they occurred on.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::{self, CopyOptions};
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let bytes = fs::copy_dir_all("assets", "backup/assets", &CopyOptions::new())?;
+/// println!("copied {} bytes", bytes);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`CopyOptions::overwrite`]: struct.CopyOptions.html#method.overwrite
+pub fn copy_dir_all<P, Q, A, B>(from: P, to: Q, options: &CopyOptions) -> io::Result<u64, B>
+    where P: AsRef<Path<A>>, Q: AsRef<Path<B>>, A: Std, B: Std,
+          B::OsError: From<A::OsError>
+{
+    let from = from.as_ref();
+    let to = to.as_ref();
+    let attr = A::lstat(from).map_err(|e| copy_error(e.map_platform(), "read", &from.display()))?;
+    if !attr.file_type().is_dir() {
+        let err = const_error!(io::ErrorKind::NotADirectory, "source is not a directory");
+        return Err(copy_error(err, "copy", &from.display()));
+    }
+    if B::is_inside(to, from)? {
+        let err = const_error!(io::ErrorKind::InvalidInput,
+                               "cannot copy a directory into itself");
+        return Err(copy_error(err, "copy", &from.display()));
+    }
+
+    let mut copy = TreeCopy {
+        options: options,
+        progress: CopyProgress::default(),
+        dirs: Vec::new(),
+    };
+    copy.dir(from, to)?;
+
+    // Directories get their permissions last, so a read-only directory
+    // doesn't keep its own contents from being copied.
+    if options.preserve_permissions {
+        while let Some((dst, perm)) = copy.dirs.pop() {
+            B::set_perm(&dst, perm).map_err(|e| copy_error(e, "set permissions of", &dst.display()))?;
+        }
+    }
+    Ok(copy.progress.bytes_copied)
+}
+
+struct TreeCopy<'a, B: Std> {
+    options: &'a CopyOptions,
+    progress: CopyProgress,
+    dirs: Vec<(PathBuf<B>, B::Permissions)>,
+}
+
+impl<'a, B: Std> TreeCopy<'a, B> {
+    fn dir<A: Std>(&mut self, from: &Path<A>, to: &Path<B>) -> io::Result<(), B>
+        where B::OsError: From<A::OsError>
+    {
+        match B::lstat(to) {
+            Ok(ref attr) if attr.file_type().is_dir() => {}
+            Ok(_) => {
+                let err = const_error!(io::ErrorKind::AlreadyExists,
+                                       "destination exists and is not a directory");
+                return Err(copy_error(err, "create directory", &to.display()));
+            }
+            Err(_) => {
+                B::DirBuilder::new().mkdir(to)
+                    .map_err(|e| copy_error(e, "create directory", &to.display()))?;
+                self.progress.dirs_created += 1;
+                self.report();
+            }
+        }
+        if self.options.preserve_permissions {
+            let perm = A::lstat(from).map(|attr| attr.perm()).map_err(|e| e.map_platform())
+                .and_then(|perm| B::permissions_from(perm, to))
+                .map_err(|e| copy_error(e, "read permissions of", &from.display()))?;
+            self.dirs.push((to.to_path_buf(), perm));
+        }
+
+        let entries = A::readdir(from)
+            .map_err(|e| copy_error(e.map_platform(), "read directory", &from.display()))?;
+        for ent in entries {
+            let ent = ent.map_err(|e| copy_error(e.map_platform(), "read directory", &from.display()))?;
+            let src = ent.path();
+            let name = ent.file_name();
+            let dst = to.join(OsStr::<B>::from_bytes(name.as_bytes()));
+            let file_type = ent.file_type()
+                .map_err(|e| copy_error(e.map_platform(), "read", &src.display()))?;
+
+            if file_type.is_symlink() {
+                self.symlink(&src, &dst)?;
+            } else if file_type.is_dir() {
+                self.dir(&src, &dst)?;
+            } else {
+                self.file(&src, &dst)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn symlink<A: Std>(&mut self, from: &Path<A>, to: &Path<B>) -> io::Result<(), B>
+        where B::OsError: From<A::OsError>
+    {
+        let target = A::readlink(from)
+            .map_err(|e| copy_error(e.map_platform(), "read link", &from.display()))?;
+        let target = Path::<B>::new(OsStr::<B>::from_bytes(target.as_os_str().as_bytes()));
+        self.make_room(to)?;
+        B::symlink(target, to).map_err(|e| copy_error(e, "create symlink", &to.display()))?;
+        self.progress.symlinks_created += 1;
+        self.report();
+        Ok(())
+    }
+
+    fn file<A: Std>(&mut self, from: &Path<A>, to: &Path<B>) -> io::Result<(), B>
+        where B::OsError: From<A::OsError>
+    {
+        self.make_room(to)?;
+
+        let mut opts = A::OpenOptions::new();
+        opts.read(true);
+        let src = A::File::open(from, &opts)
+            .map_err(|e| copy_error(e.map_platform(), "open", &from.display()))?;
+        let mut opts = B::OpenOptions::new();
+        opts.write(true);
+        opts.create_new(true);
+        let dst = B::File::open(to, &opts)
+            .map_err(|e| copy_error(e, "create", &to.display()))?;
+
+        let mut buf = [0; ::sys_common::io::DEFAULT_BUF_SIZE];
+        loop {
+            let n = match src.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(copy_error(e.map_platform(), "read", &from.display())),
+            };
+            let mut written = 0;
+            while written < n {
+                match dst.write(&buf[written..n]) {
+                    Ok(0) => {
+                        let err = const_error!(io::ErrorKind::WriteZero,
+                                               "failed to write whole buffer");
+                        return Err(copy_error(err, "write", &to.display()));
+                    }
+                    Ok(m) => written += m,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(copy_error(e, "write", &to.display())),
+                }
+            }
+            self.progress.bytes_copied += n as u64;
+        }
+
+        if self.options.preserve_permissions || self.options.preserve_timestamps {
+            let attr = src.file_attr()
+                .map_err(|e| copy_error(e.map_platform(), "read metadata of", &from.display()))?;
+            if self.options.preserve_timestamps {
+                let accessed = attr.accessed().ok().map(|t| convert_time::<A, B>(t));
+                let modified = attr.modified().ok().map(|t| convert_time::<A, B>(t));
+                dst.set_times(accessed, modified)
+                    .map_err(|e| copy_error(e, "set times of", &to.display()))?;
+            }
+            if self.options.preserve_permissions {
+                B::permissions_from(attr.perm(), to)
+                    .and_then(|perm| dst.set_permissions(perm))
+                    .map_err(|e| copy_error(e, "set permissions of", &to.display()))?;
+            }
+        }
+
+        self.progress.files_copied += 1;
+        self.report();
+        Ok(())
+    }
+
+    // Removes a file or symlink in the way of a new entry if overwriting,
+    // and fails otherwise.
+    fn make_room(&self, to: &Path<B>) -> io::Result<(), B> {
+        let attr = match B::lstat(to) {
+            Ok(attr) => attr,
+            Err(_) => return Ok(()),
+        };
+        if !self.options.overwrite || attr.file_type().is_dir() {
+            let err = const_error!(io::ErrorKind::AlreadyExists, "destination already exists");
+            return Err(copy_error(err, "copy to", &to.display()));
+        }
+        B::unlink(to).map_err(|e| copy_error(e, "remove", &to.display()))
+    }
+
+    fn report(&self) {
+        if let Some(ref progress) = self.options.progress {
+            progress(&self.progress);
+        }
+    }
+}
+
+fn copy_error<STD: Std>(err: io::Error<STD>, op: &str, path: &fmt::Display) -> io::Error<STD> {
+    err.with_context(fmt::format(format_args!("failed to {} `{}`", op, path)))
+}
+
+fn convert_time<A: Std, B: Std>(t: A::SystemTime) -> B::SystemTime {
+    match t.sub_time(&A::UNIX_EPOCH) {
+        Ok(d) => B::UNIX_EPOCH.add_duration(&d),
+        Err(d) => B::UNIX_EPOCH.sub_duration(&d),
+    }
+}
+
+// Converts permissions between backends. Between different backends only
+// the read-only flag survives, applied to the destination's current
+// permissions.
+trait PermissionsFrom<A: Std>: Std {
+    fn permissions_from(perm: A::Permissions, dst: &Path<Self>) -> io::Result<Self::Permissions, Self>;
+}
+
+impl<A: Std, B: Std> PermissionsFrom<A> for B {
+    default fn permissions_from(perm: A::Permissions, dst: &Path<B>)
+                                -> io::Result<B::Permissions, B> {
+        let mut ret = B::lstat(dst)?.perm();
+        ret.set_readonly(perm.readonly());
+        Ok(ret)
+    }
+}
+
+impl<A: Std> PermissionsFrom<A> for A {
+    fn permissions_from(perm: A::Permissions, _dst: &Path<A>) -> io::Result<A::Permissions, A> {
+        Ok(perm)
+    }
+}
+
+// Checks whether copying to `to` would copy into the source tree itself,
+// which would never end. Paths on different backends never overlap.
+trait IsInside<A: Std>: Std {
+    fn is_inside(to: &Path<Self>, from: &Path<A>) -> io::Result<bool, Self>;
+}
+
+impl<A: Std, B: Std> IsInside<A> for B {
+    default fn is_inside(_to: &Path<B>, _from: &Path<A>) -> io::Result<bool, B> {
+        Ok(false)
+    }
+}
+
+impl<A: Std> IsInside<A> for A {
+    fn is_inside(to: &Path<A>, from: &Path<A>) -> io::Result<bool, A> {
+        let from = A::canonicalize(from).map_err(|e| copy_error(e, "resolve", &from.display()))?;
+        let to = resolve_new(to).map_err(|e| copy_error(e, "resolve", &to.display()))?;
+        Ok(to.starts_with(&from))
+    }
+}
+
+// Canonicalizes a path that may not exist yet through its parent, which
+// `copy_dir_all` needs to exist anyway.
+fn resolve_new<STD: Std>(path: &Path<STD>) -> io::Result<PathBuf<STD>, STD> {
+    let err = match STD::canonicalize(path) {
+        Ok(path) => return Ok(path),
+        Err(err) => err,
+    };
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            Ok(STD::canonicalize(parent)?.join(name))
+        }
+        _ => Err(err),
+    }
+}
+
+/// How many names are tried before giving up on creating a temporary file.
+const TEMP_ATTEMPTS: usize = 1 << 16;
+
+// Returns a file name that most likely doesn't exist yet.
+//
+// There's no randomness source on `Std`, so this mixes the time, the thread
+// and a process-wide counter. Collisions are still possible and callers
+// retry with `create_new`.
+fn unique_name<STD: Std>(prefix: &OsStr<STD>, suffix: &OsStr<STD>) -> OsString<STD> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed) as u64;
+    let since_epoch = match STD::SystemTime::now().sub_time(&STD::UNIX_EPOCH) {
+        Ok(d) | Err(d) => d,
+    };
+    let mut x = since_epoch.as_secs().wrapping_mul(1_000_000_007)
+        ^ since_epoch.subsec_nanos() as u64
+        ^ count.rotate_left(40)
+        ^ (STD::current_thread_id() as u64).rotate_left(20)
+        ^ (&count as *const u64 as u64);
+    // splitmix64 finalizer
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^= x >> 31;
+
+    let mut name = OsString::new();
+    name.push(prefix);
+    name.push(fmt::format(format_args!("{:012x}", x & 0xffff_ffff_ffff)));
+    name.push(suffix);
+    name
+}
+
+// Creates a new file in `dir` named by `unique_name`, retrying on collision.
+fn create_unique<STD: Std>(dir: &Path<STD>, prefix: &OsStr<STD>, suffix: &OsStr<STD>)
+                           -> io::Result<(PathBuf<STD>, File<STD>), STD> {
+    for _ in 0..TEMP_ATTEMPTS {
+        let path = dir.join(unique_name(prefix, suffix));
+        match OpenOptions::new().read(true).write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(const_error!(io::ErrorKind::AlreadyExists, "too many temporary files exist"))
+}
+
+/// Replaces the contents of a file atomically.
+///
+/// The contents are written to a temporary file next to `path`, flushed to
+/// disk, and renamed over `path`. A crash leaves either the old or the new
+/// contents, never a mix. If `path` exists, its permissions are kept. If
+/// `path` is a symlink, the file it points to is replaced and the symlink
+/// is left in place.
+///
+/// The rename is only guaranteed to survive a crash once the parent
+/// directory has been synced too; use [`AtomicFile::sync_parent`] where that
+/// matters.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs;
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// fs::write_atomic("config.toml", b"answer = 42\n")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`AtomicFile::sync_parent`]: struct.AtomicFile.html#method.sync_parent
+pub fn write_atomic<P: AsRef<Path<STD>>, C: AsRef<[u8]>, STD: Std>(path: P, contents: C)
+                                                                   -> io::Result<(), STD> {
+    let mut file = AtomicFile::new(path)?;
+    file.write_all(contents.as_ref())?;
+    file.commit()
+}
+
+/// How many symlinks `AtomicFile` follows before giving up.
+const MAX_LINKS: usize = 40;
+
+// Follows symlinks at `path` until reaching something that isn't one, which
+// may not exist yet.
+fn resolve_links<STD: Std>(path: &Path<STD>) -> io::Result<PathBuf<STD>, STD> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_LINKS {
+        match symlink_metadata(&path) {
+            Ok(ref meta) if meta.file_type().is_symlink() => {
+                let link = read_link(&path)?;
+                path = match path.parent() {
+                    Some(dir) => dir.join(link),
+                    None => link,
+                };
+            }
+            Ok(_) => return Ok(path),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(path),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(const_error!(io::ErrorKind::FilesystemLoop, "too many levels of symbolic links"))
+}
+
+/// A writer that atomically replaces a file when committed.
+///
+/// Writes go to a uniquely named temporary file in the same directory as
+/// the target. [`commit`] syncs it to disk and renames it over the target;
+/// dropping the writer without committing removes the temporary file and
+/// leaves the target untouched.
+///
+/// If the target is a symlink, it is resolved when the writer is created
+/// and the file it points to is replaced, so the symlink itself survives.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::AtomicFile;
+/// use io::Write;
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let mut file = AtomicFile::new("index.db")?;
+/// file.sync_parent(true);
+/// file.write_all(b"...")?;
+/// file.commit()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`commit`]: #method.commit
+pub struct AtomicFile<STD: Std> {
+    file: File<STD>,
+    temp: PathBuf<STD>,
+    target: PathBuf<STD>,
+    sync_parent: bool,
+    committed: bool,
+}
+
+impl<STD: Std> AtomicFile<STD> {
+    /// Starts replacing the file at `path`.
+    ///
+    /// The temporary file is created right away, with the permissions of
+    /// the target if it exists, so this fails if the parent directory isn't
+    /// writable.
+    pub fn new<P: AsRef<Path<STD>>>(path: P) -> io::Result<AtomicFile<STD>, STD> {
+        let target = resolve_links(path.as_ref())?;
+        let (temp, file) = {
+            let name = match target.file_name() {
+                Some(name) => name,
+                None => return Err(const_error!(io::ErrorKind::InvalidInput,
+                                                "path does not name a file")),
+            };
+            let dir = target.parent().unwrap_or(Path::new(""));
+
+            let mut prefix = OsString::from(".");
+            prefix.push(name);
+            prefix.push(".");
+            create_unique(dir, &prefix, OsStr::new(".tmp"))?
+        };
+        let atomic = AtomicFile {
+            file: file,
+            temp: temp,
+            target: target,
+            sync_parent: false,
+            committed: false,
+        };
+        match metadata(&atomic.target) {
+            Ok(meta) => atomic.file.set_permissions(meta.permissions())?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(atomic)
+    }
+
+    /// Sets whether `commit` also syncs the parent directory, so the rename
+    /// itself is durable.
+    ///
+    /// Off by default. Fails at commit time on platforms that can't open
+    /// directories.
+    pub fn sync_parent(&mut self, sync: bool) -> &mut AtomicFile<STD> {
+        self.sync_parent = sync;
+        self
+    }
+
+    /// The path of the file being replaced, with symlinks resolved.
+    pub fn path(&self) -> &Path<STD> {
+        &self.target
+    }
+
+    /// Gets a reference to the temporary file.
+    pub fn as_file(&self) -> &File<STD> {
+        &self.file
+    }
+
+    /// Syncs the written contents to disk and renames them over the target.
+    ///
+    /// On error the temporary file is removed and the target is left as it
+    /// was, unless the error came from syncing the parent directory after
+    /// the rename.
+    pub fn commit(mut self) -> io::Result<(), STD> {
+        self.file.flush()?;
+        self.file.sync_all()?;
+        rename(&self.temp, &self.target)?;
+        self.committed = true;
+
+        if self.sync_parent {
+            let dir = match self.target.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl<STD: Std> Write<STD> for AtomicFile<STD> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize, STD> {
+        self.file.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize, STD> {
+        self.file.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<(), STD> {
+        self.file.flush()
+    }
+}
+
+impl<STD: Std> Drop for AtomicFile<STD> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = remove_file(&self.temp);
+        }
+    }
+}
+
+impl<STD: Std> fmt::Debug for AtomicFile<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AtomicFile")
+            .field("target", &self.target)
+            .field("temp", &self.temp)
+            .finish()
+    }
+}
+
+// Creates a new directory in `dir` named by `unique_name`, retrying on
+// collision.
+fn create_unique_dir<STD: Std>(dir: &Path<STD>, prefix: &OsStr<STD>)
+                               -> io::Result<PathBuf<STD>, STD> {
+    for _ in 0..TEMP_ATTEMPTS {
+        let path = dir.join(unique_name(prefix, OsStr::new("")));
+        match create_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(const_error!(io::ErrorKind::AlreadyExists, "too many temporary files exist"))
+}
+
+/// Creates an anonymous temporary file in [`env::temp_dir`].
+///
+/// The file is removed from the directory right after it's created, so it
+/// goes away once the returned handle is closed. Use [`NamedTempFile`] when
+/// the file needs a path.
+///
+/// # Platform-specific behavior
+///
+/// On platforms that can't remove open files the name is left in place
+/// until the handle is dropped, and may then be leaked.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs;
+/// use io::Write;
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let mut scratch = fs::tempfile()?;
+/// scratch.write_all(b"scratch space")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`env::temp_dir`]: ../env/fn.temp_dir.html
+/// [`NamedTempFile`]: struct.NamedTempFile.html
+pub fn tempfile<STD: Std>() -> io::Result<File<STD>, STD> {
+    tempfile_in(STD::temp_dir())
+}
+
+/// Like [`tempfile`], but creates the file in `dir`.
+///
+/// [`tempfile`]: fn.tempfile.html
+pub fn tempfile_in<P: AsRef<Path<STD>>, STD: Std>(dir: P) -> io::Result<File<STD>, STD> {
+    let (path, file) = create_unique(dir.as_ref(), OsStr::new(".tmp"), OsStr::new(""))?;
+    let _ = remove_file(&path);
+    Ok(file)
+}
+
+/// A temporary file with a path, removed when dropped.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::NamedTempFile;
+/// use io::Write;
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let mut file = NamedTempFile::new_in("out")?;
+/// file.write_all(b"partial results")?;
+/// file.persist("out/results.txt")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct NamedTempFile<STD: Std> {
+    // `None` once persisted.
+    path: Option<PathBuf<STD>>,
+    // `None` once handed out.
+    file: Option<File<STD>>,
+}
+
+impl<STD: Std> NamedTempFile<STD> {
+    /// Creates a temporary file in [`env::temp_dir`].
+    ///
+    /// [`env::temp_dir`]: ../env/fn.temp_dir.html
+    pub fn new() -> io::Result<NamedTempFile<STD>, STD> {
+        NamedTempFile::new_in(STD::temp_dir())
+    }
+
+    /// Creates a temporary file in `dir`.
+    ///
+    /// The file is opened for reading and writing, with a unique name
+    /// created by `OpenOptions::create_new` so an existing file is never
+    /// reused.
+    pub fn new_in<P: AsRef<Path<STD>>>(dir: P) -> io::Result<NamedTempFile<STD>, STD> {
+        let (path, file) = create_unique(dir.as_ref(), OsStr::new(".tmp"), OsStr::new(""))?;
+        Ok(NamedTempFile {
+            path: Some(path),
+            file: Some(file),
+        })
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &Path<STD> {
+        self.path.as_ref().unwrap()
+    }
+
+    /// Gets a reference to the file.
+    pub fn as_file(&self) -> &File<STD> {
+        self.file.as_ref().unwrap()
+    }
+
+    /// Gets a mutable reference to the file.
+    pub fn as_file_mut(&mut self) -> &mut File<STD> {
+        self.file.as_mut().unwrap()
+    }
+
+    /// Moves the file to `new_path`, replacing anything there, and keeps
+    /// it, returning the open handle.
+    ///
+    /// `new_path` must be on the same filesystem. If renaming fails, the
+    /// temporary file is handed back in the error, still at its old path.
+    pub fn persist<P: AsRef<Path<STD>>>(mut self, new_path: P)
+                                        -> Result<File<STD>, PersistError<STD>> {
+        if let Err(e) = rename(self.path(), new_path.as_ref()) {
+            return Err(PersistError { error: e, file: self });
+        }
+        self.path = None;
+        Ok(self.into_file())
+    }
+
+    /// Keeps the file where it is, returning the open handle and its path.
+    pub fn keep(mut self) -> (File<STD>, PathBuf<STD>) {
+        let path = self.path.take().unwrap();
+        (self.into_file(), path)
+    }
+
+    /// Removes the file now, reporting any error that dropping would
+    /// ignore.
+    pub fn close(mut self) -> io::Result<(), STD> {
+        let path = self.path.take().unwrap();
+        remove_file(&path)
+    }
+
+    // Must only be called once `path` has been taken.
+    fn into_file(mut self) -> File<STD> {
+        self.file.take().unwrap()
+    }
+}
+
+impl<STD: Std> Drop for NamedTempFile<STD> {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            let _ = remove_file(path);
+        }
+    }
+}
+
+impl<STD: Std> Read<STD> for NamedTempFile<STD> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize, STD> {
+        self.as_file_mut().read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize, STD> {
+        self.as_file_mut().read_vectored(bufs)
+    }
+
+    fn read_buf(&mut self, cursor: BorrowedCursor) -> io::Result<(), STD> {
+        self.as_file_mut().read_buf(cursor)
+    }
+}
+
+impl<STD: Std> Write<STD> for NamedTempFile<STD> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize, STD> {
+        self.as_file_mut().write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize, STD> {
+        self.as_file_mut().write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<(), STD> {
+        self.as_file_mut().flush()
+    }
+}
+
+impl<STD: Std> Seek<STD> for NamedTempFile<STD> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64, STD> {
+        self.as_file_mut().seek(pos)
+    }
+}
+
+impl<STD: Std> fmt::Debug for NamedTempFile<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NamedTempFile")
+            .field("path", &self.path)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+/// The error returned by [`NamedTempFile::persist`], holding on to the
+/// temporary file so it can be retried or kept elsewhere.
+///
+/// Dropping it removes the temporary file, as dropping the `NamedTempFile`
+/// would.
+///
+/// [`NamedTempFile::persist`]: struct.NamedTempFile.html#method.persist
+pub struct PersistError<STD: Std> {
+    /// The error from renaming the file.
+    pub error: io::Error<STD>,
+    /// The temporary file, still at its old path.
+    pub file: NamedTempFile<STD>,
+}
+
+impl<STD: Std> From<PersistError<STD>> for io::Error<STD> {
+    fn from(err: PersistError<STD>) -> io::Error<STD> {
+        err.error
+    }
+}
+
+impl<STD: Std> fmt::Debug for PersistError<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PersistError")
+            .field("error", &self.error)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+impl<STD: Std> fmt::Display for PersistError<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl<STD: Std> error::Error for PersistError<STD> {
+    fn description(&self) -> &str {
+        error::Error::description(&self.error)
+    }
+
+    // `Display` already shows the rename error, so its sources come next.
+    fn source(&self) -> Option<&error::Error> {
+        error::Error::source(&self.error)
+    }
+}
+
+/// A temporary directory, removed with everything in it when dropped.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::{self, TempDir};
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let dir = TempDir::new()?;
+/// fs::write(dir.path().join("a.txt"), b"a")?;
+/// // `dir` and `a.txt` are removed here
+/// # Ok(())
+/// # }
+/// ```
+pub struct TempDir<STD: Std> {
+    // `None` once persisted or closed.
+    path: Option<PathBuf<STD>>,
+}
+
+impl<STD: Std> TempDir<STD> {
+    /// Creates a temporary directory in [`env::temp_dir`].
+    ///
+    /// [`env::temp_dir`]: ../env/fn.temp_dir.html
+    pub fn new() -> io::Result<TempDir<STD>, STD> {
+        TempDir::new_in(STD::temp_dir())
+    }
+
+    /// Creates a temporary directory in `dir`, with a name no other
+    /// directory has.
+    pub fn new_in<P: AsRef<Path<STD>>>(dir: P) -> io::Result<TempDir<STD>, STD> {
+        let path = create_unique_dir(dir.as_ref(), OsStr::new(".tmp"))?;
+        Ok(TempDir { path: Some(path) })
+    }
+
+    /// The path of the directory.
+    pub fn path(&self) -> &Path<STD> {
+        self.path.as_ref().unwrap()
+    }
+
+    /// Keeps the directory and its contents, returning its path.
+    pub fn persist(mut self) -> PathBuf<STD> {
+        self.path.take().unwrap()
+    }
+
+    /// Removes the directory now, reporting any error that dropping would
+    /// ignore.
+    pub fn close(mut self) -> io::Result<(), STD> {
+        let path = self.path.take().unwrap();
+        remove_dir_all(&path)
+    }
+}
+
+impl<STD: Std> AsRef<Path<STD>> for TempDir<STD> {
+    fn as_ref(&self) -> &Path<STD> {
+        self.path()
+    }
+}
+
+impl<STD: Std> Drop for TempDir<STD> {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            let _ = remove_dir_all(path);
+        }
+    }
+}
+
+impl<STD: Std> fmt::Debug for TempDir<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TempDir").field("path", &self.path).finish()
+    }
+}
+
+/// Filesystem operations whose errors name the path they failed on.
+///
+/// Each function here does the same as its namesake in `fs`, but on failure
+/// attaches the operation and path(s) to the error with
+/// [`io::Error::with_context`], so it reads like ``failed to open
+/// `foo.txt`: No such file or directory (os error 2)``. The error's `kind`
+/// and `raw_os_error` are left untouched.
+///
+/// [`io::Error::with_context`]: ../../io/struct.Error.html#method.with_context
+///
+/// # Examples
+///
+/// ```ignore
+/// use fs::context;
+///
+/// # fn foo() -> ::std::io::Result<()> {
+/// let contents = context::read_string("config.toml")?;
+/// context::rename("a.txt", "b.txt")?;
+/// # Ok(())
+/// # }
+/// ```
+pub mod context {
+    use prelude::*;
+    use traits::Std;
+
+    use fmt;
+    use io;
+    use path::{Path, PathBuf};
+    use super::{File, Metadata, Permissions, ReadDir};
+
+    fn one<T, STD: Std>(ret: io::Result<T, STD>, op: &str, path: &Path<STD>)
+                        -> io::Result<T, STD> {
+        ret.map_err(|e| {
+            e.with_context(fmt::format(format_args!("failed to {} `{}`", op, path.display())))
+        })
+    }
+
+    fn two<T, STD: Std>(ret: io::Result<T, STD>, op: &str, from: &Path<STD>, to: &Path<STD>)
+                        -> io::Result<T, STD> {
+        ret.map_err(|e| {
+            e.with_context(fmt::format(format_args!("failed to {} `{}` to `{}`",
+                                                    op, from.display(), to.display())))
+        })
+    }
+
+    /// Like [`File::open`](../struct.File.html#method.open).
+    pub fn open<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<File<STD>, STD> {
+        let path = path.as_ref();
+        one(File::open(path), "open", path)
+    }
+
+    /// Like [`File::create`](../struct.File.html#method.create).
+    pub fn create<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<File<STD>, STD> {
+        let path = path.as_ref();
+        one(File::create(path), "create", path)
+    }
+
+    /// Like [`fs::read`](../fn.read.html).
+    pub fn read<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<Vec<u8>, STD> {
+        let path = path.as_ref();
+        one(super::read(path), "read", path)
+    }
+
+    /// Like [`fs::read_string`](../fn.read_string.html).
+    pub fn read_string<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<String, STD> {
+        let path = path.as_ref();
+        one(super::read_string(path), "read", path)
+    }
+
+    /// Like [`fs::write`](../fn.write.html).
+    pub fn write<P: AsRef<Path<STD>>, C: AsRef<[u8]>, STD: Std>(path: P, contents: C)
+                                                              -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::write(path, contents), "write", path)
+    }
+
+    /// Like [`fs::metadata`](../fn.metadata.html).
+    pub fn metadata<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<Metadata<STD>, STD> {
+        let path = path.as_ref();
+        one(super::metadata(path), "query metadata of", path)
+    }
+
+    /// Like [`fs::symlink_metadata`](../fn.symlink_metadata.html).
+    pub fn symlink_metadata<P: AsRef<Path<STD>>, STD: Std>(path: P)
+                                                         -> io::Result<Metadata<STD>, STD> {
+        let path = path.as_ref();
+        one(super::symlink_metadata(path), "query metadata of", path)
+    }
+
+    /// Like [`fs::remove_file`](../fn.remove_file.html).
+    pub fn remove_file<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::remove_file(path), "remove file", path)
+    }
+
+    /// Like [`fs::rename`](../fn.rename.html).
+    pub fn rename<P: AsRef<Path<STD>>, Q: AsRef<Path<STD>>, STD: Std>(from: P, to: Q)
+                                                                    -> io::Result<(), STD> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        two(super::rename(from, to), "rename", from, to)
+    }
+
+    /// Like [`fs::copy`](../fn.copy.html).
+    pub fn copy<P: AsRef<Path<STD>>, Q: AsRef<Path<STD>>, STD: Std>(from: P, to: Q)
+                                                                  -> io::Result<u64, STD> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        two(super::copy(from, to), "copy", from, to)
+    }
+
+    /// Like [`fs::hard_link`](../fn.hard_link.html).
+    pub fn hard_link<P: AsRef<Path<STD>>, Q: AsRef<Path<STD>>, STD: Std>(src: P, dst: Q)
+                                                                       -> io::Result<(), STD> {
+        let (src, dst) = (src.as_ref(), dst.as_ref());
+        two(super::hard_link(src, dst), "hard link", src, dst)
+    }
+
+    /// Like [`fs::soft_link`](../fn.soft_link.html).
+    pub fn soft_link<P: AsRef<Path<STD>>, Q: AsRef<Path<STD>>, STD: Std>(src: P, dst: Q)
+                                                                       -> io::Result<(), STD> {
+        let (src, dst) = (src.as_ref(), dst.as_ref());
+        two(super::soft_link(src, dst), "symlink", src, dst)
+    }
+
+    /// Like [`fs::read_link`](../fn.read_link.html).
+    pub fn read_link<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<PathBuf<STD>, STD> {
+        let path = path.as_ref();
+        one(super::read_link(path), "read link", path)
+    }
+
+    /// Like [`fs::canonicalize`](../fn.canonicalize.html).
+    pub fn canonicalize<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<PathBuf<STD>, STD> {
+        let path = path.as_ref();
+        one(super::canonicalize(path), "canonicalize", path)
+    }
+
+    /// Like [`fs::create_dir`](../fn.create_dir.html).
+    pub fn create_dir<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::create_dir(path), "create directory", path)
+    }
+
+    /// Like [`fs::create_dir_all`](../fn.create_dir_all.html).
+    pub fn create_dir_all<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::create_dir_all(path), "create directory", path)
+    }
+
+    /// Like [`fs::remove_dir`](../fn.remove_dir.html).
+    pub fn remove_dir<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::remove_dir(path), "remove directory", path)
+    }
+
+    /// Like [`fs::remove_dir_all`](../fn.remove_dir_all.html).
+    pub fn remove_dir_all<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::remove_dir_all(path), "remove directory", path)
+    }
+
+    /// Like [`fs::read_dir`](../fn.read_dir.html).
+    pub fn read_dir<P: AsRef<Path<STD>>, STD: Std>(path: P) -> io::Result<ReadDir<STD>, STD> {
+        let path = path.as_ref();
+        one(super::read_dir(path), "read directory", path)
+    }
+
+    /// Like [`fs::set_permissions`](../fn.set_permissions.html).
+    pub fn set_permissions<P: AsRef<Path<STD>>, STD: Std>(path: P, perm: Permissions<STD>)
+                                                        -> io::Result<(), STD> {
+        let path = path.as_ref();
+        one(super::set_permissions(path, perm), "set permissions of", path)
+    }
+}
+
+#[cfg(all(test, not(any(target_os = "cloudabi", target_os = "emscripten"))))]
+mod tests {
+    use io::prelude::*;
+
+    use fs::{self, File, OpenOptions};
+    use io::{ErrorKind, SeekFrom};
+    use path::{Path, PathBuf};
+    use rand::{StdRng, Rng};
+    use str;
+    use sys_common::io::test::{TempDir, tmpdir};
+    use thread;
+
+    #[cfg(windows)]
+    use os::windows::fs::{symlink_dir, symlink_file};
+    #[cfg(windows)]
+    use sys::fs::symlink_junction;
+    #[cfg(unix)]
+    use os::unix::fs::symlink as symlink_dir;
+    #[cfg(unix)]
+    use os::unix::fs::symlink as symlink_file;
+    #[cfg(unix)]
+    use os::unix::fs::symlink as symlink_junction;
+
+    macro_rules! check { ($e:expr) => (
+        match $e {
+            Ok(t) => t,
+            Err(e) => panic!("{} failed with: {}", stringify!($e), e),
+        }
+    ) }
 
     #[cfg(windows)]
     macro_rules! error { ($e:expr, $s:expr) => (
@@ -2295,6 +4497,672 @@ mod tests {
         check!(fs::remove_file(&filename));
     }
 
+    #[test]
+    fn file_test_io_read_write_at_concurrent() {
+        use alloc::arc::Arc;
+
+        let tmpdir = tmpdir();
+        let filename = tmpdir.join("file_rt_io_file_test_read_write_at_concurrent.txt");
+        let file = Arc::new(check!(OpenOptions::new().create_new(true).write(true)
+                                                     .read(true).open(&filename)));
+        let mut join = vec!();
+        for i in 0..8u8 {
+            let file = file.clone();
+            join.push(thread::spawn(move || {
+                let offset = i as u64 * 64;
+                for _ in 0..100 {
+                    assert_eq!(check!(file.write_at(&[i; 64], offset)), 64);
+                    let mut buf = [0; 64];
+                    assert_eq!(check!(file.read_at(&mut buf, offset)), 64);
+                    assert!(buf.iter().all(|&b| b == i));
+                }
+            }))
+        }
+        join.drain(..).map(|join| join.join().unwrap()).count();
+
+        let mut file = match Arc::try_unwrap(file) {
+            Ok(file) => file,
+            Err(_) => panic!("file is still shared"),
+        };
+        assert_eq!(check!(file.seek(SeekFrom::Current(0))), 0);
+        let mut contents = Vec::new();
+        check!(file.read_to_end(&mut contents));
+        assert_eq!(contents.len(), 8 * 64);
+        for (i, chunk) in contents.chunks(64).enumerate() {
+            assert!(chunk.iter().all(|&b| b == i as u8));
+        }
+    }
+
+    #[test]
+    fn async_file_roundtrip() {
+        use io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+        use task::block_on;
+
+        let tmpdir = tmpdir();
+        let filename = tmpdir.join("async_file_roundtrip.txt");
+        {
+            let mut file = check!(block_on(fs::AsyncFile::create(&filename)));
+            check!(block_on(file.write_all(b"hello world")));
+            check!(block_on(file.flush()));
+        }
+        let mut file = check!(block_on(fs::AsyncFile::open(&filename)));
+        let mut buf = Vec::new();
+        assert_eq!(check!(block_on(file.read_to_end(&mut buf))), 11);
+        assert_eq!(buf, b"hello world");
+        assert_eq!(check!(block_on(file.seek(SeekFrom::Start(6)))), 6);
+        let mut buf = [0; 5];
+        assert_eq!(check!(block_on(file.read(&mut buf))), 5);
+        assert_eq!(&buf, b"world");
+    }
+
+    #[test]
+    fn batch_empty() {
+        let batch = fs::Batch::new();
+        assert!(batch.is_empty());
+        assert!(check!(batch.submit()).is_empty());
+    }
+
+    #[test]
+    fn batch_roundtrip() {
+        use fs::BatchResult;
+
+        let tmpdir = tmpdir();
+        let filename = tmpdir.join("batch_roundtrip.txt");
+        let file = check!(OpenOptions::new().create_new(true).write(true)
+                                            .read(true).open(&filename));
+        {
+            let mut batch = fs::Batch::new();
+            let a = batch.write_at(&file, b"hello", 0);
+            let b = batch.write_at(&file, b"world", 5);
+            assert_eq!(batch.len(), 2);
+            let results = check!(batch.submit());
+            match results[a] {
+                Ok(BatchResult::Written(5)) => {}
+                ref r => panic!("unexpected result: {:?}", r),
+            }
+            match results[b] {
+                Ok(BatchResult::Written(5)) => {}
+                ref r => panic!("unexpected result: {:?}", r),
+            }
+        }
+
+        let mut buf = [0; 10];
+        let missing = tmpdir.join("missing");
+        let options = OpenOptions::new().read(true).clone();
+        {
+            let mut batch = fs::Batch::with_capacity(5);
+            let sync = batch.sync_all(&file);
+            let read = batch.read_at(&file, &mut buf, 0);
+            let meta = batch.metadata(&filename);
+            let open = batch.open(&filename, &options);
+            let err = batch.metadata(&missing);
+            let mut results = check!(batch.submit());
+            assert_eq!(results.len(), 5);
+            match results[sync] {
+                Ok(BatchResult::Synced) => {}
+                ref r => panic!("unexpected result: {:?}", r),
+            }
+            match results[read] {
+                Ok(BatchResult::Read(10)) => {}
+                ref r => panic!("unexpected result: {:?}", r),
+            }
+            match results[meta] {
+                Ok(BatchResult::Metadata(ref meta)) => assert_eq!(meta.len(), 10),
+                ref r => panic!("unexpected result: {:?}", r),
+            }
+            match results[err] {
+                Err(ref e) => assert_eq!(e.kind(), ErrorKind::NotFound),
+                ref r => panic!("unexpected result: {:?}", r),
+            }
+            let opened = match results.swap_remove(open) {
+                Ok(BatchResult::Opened(file)) => file,
+                r => panic!("unexpected result: {:?}", r),
+            };
+
+            let mut batch = fs::Batch::new();
+            let close = batch.close(opened);
+            match check!(batch.submit()).pop() {
+                Some(Ok(BatchResult::Closed)) => assert_eq!(close, 0),
+                r => panic!("unexpected result: {:?}", r),
+            }
+        }
+        assert_eq!(&buf, b"helloworld");
+    }
+
+    #[test]
+    fn context_names_the_path() {
+        use fs::context;
+
+        let tmpdir = tmpdir();
+        let missing = tmpdir.join("context_missing.txt");
+        let plain = fs::File::open(&missing).unwrap_err();
+        let err = context::open(&missing).unwrap_err();
+        let msg = format!("failed to open `{}`", missing.display());
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.raw_os_error(), plain.raw_os_error());
+        assert_eq!(err.context(), Some(&msg[..]));
+        assert_eq!(err.to_string(), format!("{}: {}", msg, plain));
+
+        let other = tmpdir.join("context_other.txt");
+        let err = context::rename(&missing, &other).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.context(), Some(&format!("failed to rename `{}` to `{}`",
+                                                missing.display(), other.display())[..]));
+    }
+
+    #[test]
+    fn context_passes_success_through() {
+        use fs::context;
+
+        let tmpdir = tmpdir();
+        let file = tmpdir.join("context_ok.txt");
+        check!(context::write(&file, b"hello"));
+        assert_eq!(check!(context::read_string(&file)), "hello");
+        assert_eq!(check!(context::metadata(&file)).len(), 5);
+        check!(context::remove_file(&file));
+        assert!(context::remove_file(&file).unwrap_err().context().is_some());
+    }
+
+    // Builds root/{a/{b/{f}, x}, hidden/y} and returns its root.
+    fn walk_tree(tmpdir: &TempDir) -> PathBuf {
+        let root = tmpdir.join("walk");
+        check!(fs::create_dir_all(root.join("a").join("b")));
+        check!(fs::create_dir_all(root.join("hidden")));
+        check!(File::create(root.join("a").join("b").join("f")));
+        check!(File::create(root.join("a").join("x")));
+        check!(File::create(root.join("hidden").join("y")));
+        root
+    }
+
+    fn walked(walk: fs::WalkDir) -> Vec<(usize, String)> {
+        walk.map(|e| {
+            let e = check!(e);
+            (e.depth(), e.file_name().to_str().unwrap().to_owned())
+        }).collect()
+    }
+
+    fn pairs(expected: &[(usize, &str)]) -> Vec<(usize, String)> {
+        expected.iter().map(|&(d, n)| (d, n.to_owned())).collect()
+    }
+
+    #[test]
+    fn walk_dir_depth_limits() {
+        let tmpdir = tmpdir();
+        let root = walk_tree(&tmpdir);
+
+        assert_eq!(walked(fs::walk_dir(&root).sort_by_file_name()),
+                   pairs(&[(0, "walk"), (1, "a"), (2, "b"), (3, "f"), (2, "x"),
+                           (1, "hidden"), (2, "y")]));
+        assert_eq!(walked(fs::walk_dir(&root).sort_by_file_name().max_depth(1)),
+                   pairs(&[(0, "walk"), (1, "a"), (1, "hidden")]));
+        assert_eq!(walked(fs::walk_dir(&root).sort_by_file_name().min_depth(2)),
+                   pairs(&[(2, "b"), (3, "f"), (2, "x"), (2, "y")]));
+        assert_eq!(walked(fs::walk_dir(&root).min_depth(1).max_depth(0)), pairs(&[]));
+    }
+
+    #[test]
+    fn walk_dir_contents_first() {
+        let tmpdir = tmpdir();
+        let root = walk_tree(&tmpdir);
+
+        assert_eq!(walked(fs::walk_dir(&root).sort_by_file_name().contents_first(true)),
+                   pairs(&[(3, "f"), (2, "b"), (2, "x"), (1, "a"),
+                           (2, "y"), (1, "hidden"), (0, "walk")]));
+    }
+
+    #[test]
+    fn walk_dir_filter_prunes() {
+        let tmpdir = tmpdir();
+        let root = walk_tree(&tmpdir);
+
+        let walk = fs::walk_dir(&root).sort_by_file_name().filter_entry(|e| {
+            e.file_name().to_str() != Some("hidden") && e.file_name().to_str() != Some("b")
+        });
+        assert_eq!(walked(walk), pairs(&[(0, "walk"), (1, "a"), (2, "x")]));
+    }
+
+    #[test]
+    fn walk_dir_errors() {
+        let tmpdir = tmpdir();
+        let missing = tmpdir.join("missing");
+
+        let errs: Vec<_> = fs::walk_dir(&missing).collect();
+        assert_eq!(errs.len(), 1);
+        let err = errs.into_iter().next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.context().unwrap().contains("failed to walk"));
+
+        // A file as the root with min_depth > 0 yields nothing, not an error.
+        let file = tmpdir.join("file");
+        check!(File::create(&file));
+        assert_eq!(fs::walk_dir(&file).min_depth(1).count(), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn walk_dir_unreadable_root_reports_error_below_min_depth() {
+        use os::unix::fs::PermissionsExt;
+
+        let tmpdir = tmpdir();
+        let root = walk_tree(&tmpdir);
+        check!(fs::set_permissions(&root, fs::Permissions::from_mode(0)));
+        let ret: Vec<_> = fs::walk_dir(&root).min_depth(1).collect();
+        check!(fs::set_permissions(&root, fs::Permissions::from_mode(0o755)));
+
+        // Skip when running with permissions to read the directory anyway.
+        if ret.iter().all(|e| e.is_ok()) {
+            return;
+        }
+        assert_eq!(ret.len(), 1);
+        assert!(ret[0].as_ref().unwrap_err().context().unwrap().contains("failed to walk"));
+    }
+
+    #[test]
+    fn walk_dir_detects_loops() {
+        let tmpdir = tmpdir();
+        if !got_symlink_permission(&tmpdir) { return };
+
+        let root = walk_tree(&tmpdir);
+        check!(symlink_dir(&root, root.join("a").join("loop")));
+
+        // Not followed, the link is just an entry.
+        let names = walked(fs::walk_dir(&root));
+        assert!(names.contains(&(2, "loop".to_owned())));
+
+        let mut loops = 0;
+        for e in fs::walk_dir(&root).follow_links(true) {
+            match e {
+                Ok(e) => assert!(e.depth() < 4),
+                Err(e) => {
+                    assert_eq!(e.kind(), ErrorKind::FilesystemLoop);
+                    loops += 1;
+                }
+            }
+        }
+        assert_eq!(loops, 1);
+    }
+
+    // Builds src/{file, sub/nested} and returns src.
+    fn copy_tree(tmpdir: &TempDir) -> PathBuf {
+        let src = tmpdir.join("copy_src");
+        check!(fs::create_dir_all(src.join("sub")));
+        check!(fs::write(src.join("file"), b"file"));
+        check!(fs::write(src.join("sub").join("nested"), b"nested"));
+        src
+    }
+
+    #[test]
+    fn copy_dir_all_copies_tree() {
+        let tmpdir = tmpdir();
+        let src = copy_tree(&tmpdir);
+        let dst = tmpdir.join("copy_dst");
+
+        assert_eq!(check!(fs::copy_dir_all(&src, &dst, &fs::CopyOptions::new())), 10);
+        assert_eq!(check!(fs::read(dst.join("file"))), b"file");
+        assert_eq!(check!(fs::read(dst.join("sub").join("nested"))), b"nested");
+    }
+
+    #[test]
+    fn copy_dir_all_refuses_to_overwrite() {
+        let tmpdir = tmpdir();
+        let src = copy_tree(&tmpdir);
+        let dst = tmpdir.join("copy_dst");
+        check!(fs::create_dir(&dst));
+        check!(fs::write(dst.join("file"), b"old"));
+
+        let err = fs::copy_dir_all(&src, &dst, &fs::CopyOptions::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(check!(fs::read(dst.join("file"))), b"old");
+
+        let mut options = fs::CopyOptions::new();
+        options.overwrite(true);
+        check!(fs::copy_dir_all(&src, &dst, &options));
+        assert_eq!(check!(fs::read(dst.join("file"))), b"file");
+    }
+
+    #[test]
+    fn copy_dir_all_rejects_copy_into_itself() {
+        let tmpdir = tmpdir();
+        let src = copy_tree(&tmpdir);
+
+        let inside = src.join("sub").join("copy");
+        let err = fs::copy_dir_all(&src, &inside, &fs::CopyOptions::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(!inside.exists());
+
+        let mut options = fs::CopyOptions::new();
+        options.overwrite(true);
+        let err = fs::copy_dir_all(&src, &src, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        // A sibling sharing a name prefix is not inside.
+        check!(fs::copy_dir_all(&src, tmpdir.join("copy_src2"), &fs::CopyOptions::new()));
+    }
+
+    #[test]
+    fn copy_dir_all_recreates_symlinks() {
+        let tmpdir = tmpdir();
+        if !got_symlink_permission(&tmpdir) { return };
+
+        let src = copy_tree(&tmpdir);
+        check!(symlink_file("file", src.join("link")));
+        check!(symlink_file("missing", src.join("dangling")));
+        let dst = tmpdir.join("copy_dst");
+
+        let bytes = check!(fs::copy_dir_all(&src, &dst, &fs::CopyOptions::new()));
+        assert_eq!(bytes, 10);
+        for name in &["link", "dangling"] {
+            let link = dst.join(name);
+            assert!(check!(fs::symlink_metadata(&link)).file_type().is_symlink());
+        }
+        assert_eq!(check!(fs::read_link(dst.join("link"))), Path::new("file"));
+        assert_eq!(check!(fs::read_link(dst.join("dangling"))), Path::new("missing"));
+        assert_eq!(check!(fs::read(dst.join("link"))), b"file");
+    }
+
+    #[test]
+    fn copy_dir_all_preserves_timestamps() {
+        use time::Duration;
+
+        let tmpdir = tmpdir();
+        let src = copy_tree(&tmpdir);
+        let dst = tmpdir.join("copy_dst");
+        // Make sure new files get a different modification time.
+        thread::sleep(Duration::from_millis(100));
+
+        let mut options = fs::CopyOptions::new();
+        options.preserve_timestamps(true);
+        match fs::copy_dir_all(&src, &dst, &options) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == ErrorKind::Unsupported => return,
+            Err(e) => panic!("{}", e),
+        }
+        let src_time = check!(check!(fs::metadata(src.join("file"))).modified());
+        let dst_time = check!(check!(fs::metadata(dst.join("file"))).modified());
+        assert_eq!(src_time, dst_time);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_dir_all_preserves_permissions() {
+        use os::unix::fs::PermissionsExt;
+
+        let tmpdir = tmpdir();
+        let src = copy_tree(&tmpdir);
+        let dst = tmpdir.join("copy_dst");
+        check!(fs::set_permissions(src.join("file"), fs::Permissions::from_mode(0o640)));
+        check!(fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o500)));
+
+        let mut options = fs::CopyOptions::new();
+        options.preserve_permissions(true);
+        let ret = fs::copy_dir_all(&src, &dst, &options);
+        check!(fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o755)));
+        check!(ret);
+
+        let mode = |p: PathBuf| check!(fs::metadata(p)).permissions().mode() & 0o777;
+        assert_eq!(mode(dst.join("file")), 0o640);
+        assert_eq!(mode(dst.join("sub")), 0o500);
+        assert_eq!(check!(fs::read(dst.join("sub").join("nested"))), b"nested");
+        check!(fs::set_permissions(dst.join("sub"), fs::Permissions::from_mode(0o755)));
+    }
+
+    #[test]
+    fn write_atomic_creates_and_replaces() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("atomic.txt");
+
+        check!(fs::write_atomic(&path, b"first"));
+        assert_eq!(check!(fs::read(&path)), b"first");
+        check!(fs::write_atomic(&path, b"second"));
+        assert_eq!(check!(fs::read(&path)), b"second");
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 1);
+    }
+
+    #[test]
+    fn atomic_file_commit_and_drop() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("atomic.txt");
+        check!(fs::write(&path, b"old"));
+
+        {
+            let mut file = check!(fs::AtomicFile::new(&path));
+            check!(file.write_all(b"discarded"));
+        }
+        assert_eq!(check!(fs::read(&path)), b"old");
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 1);
+
+        let mut file = check!(fs::AtomicFile::new(&path));
+        check!(file.write_all(b"new"));
+        assert_eq!(check!(fs::read(&path)), b"old");
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 2);
+        check!(file.commit());
+        assert_eq!(check!(fs::read(&path)), b"new");
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn atomic_file_keeps_permissions() {
+        use os::unix::fs::PermissionsExt;
+
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("atomic.txt");
+        check!(fs::write(&path, b"old"));
+        check!(fs::set_permissions(&path, fs::Permissions::from_mode(0o640)));
+
+        let mut file = check!(fs::AtomicFile::new(&path));
+        // Already set on the temporary file before anything is written.
+        assert_eq!(check!(file.as_file().metadata()).permissions().mode() & 0o777, 0o640);
+        check!(file.write_all(b"new"));
+        check!(file.commit());
+        assert_eq!(check!(fs::metadata(&path)).permissions().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn atomic_file_replaces_symlink_target() {
+        let tmpdir = tmpdir();
+        if !got_symlink_permission(&tmpdir) { return };
+
+        let target = tmpdir.join("target.txt");
+        let link = tmpdir.join("link.txt");
+        check!(fs::write(&target, b"old"));
+        check!(symlink_file(&target, &link));
+
+        check!(fs::write_atomic(&link, b"new"));
+        assert!(check!(fs::symlink_metadata(&link)).file_type().is_symlink());
+        assert_eq!(check!(fs::read(&target)), b"new");
+
+        // A dangling symlink gets its target created.
+        let missing = tmpdir.join("missing.txt");
+        let dangling = tmpdir.join("dangling.txt");
+        check!(symlink_file(&missing, &dangling));
+        check!(fs::write_atomic(&dangling, b"created"));
+        assert!(check!(fs::symlink_metadata(&dangling)).file_type().is_symlink());
+        assert_eq!(check!(fs::read(&missing)), b"created");
+    }
+
+    #[test]
+    fn tempfile_leaves_no_entry() {
+        let tmpdir = tmpdir();
+        let mut file = check!(fs::tempfile_in(tmpdir.path()));
+        check!(file.write_all(b"scratch"));
+        check!(file.seek(SeekFrom::Start(0)));
+        let mut buf = Vec::new();
+        check!(file.read_to_end(&mut buf));
+        assert_eq!(buf, b"scratch");
+        // Platforms that can't remove open files keep the name until the
+        // handle is closed.
+        drop(file);
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 0);
+
+        drop(check!(fs::tempfile()));
+    }
+
+    #[test]
+    fn named_temp_file_drop_keep_close() {
+        let tmpdir = tmpdir();
+
+        let temp = check!(fs::NamedTempFile::new_in(tmpdir.path()));
+        let path = temp.path().to_path_buf();
+        assert!(path.starts_with(tmpdir.path()));
+        assert!(path.is_file());
+        drop(temp);
+        assert!(!path.exists());
+
+        let mut temp = check!(fs::NamedTempFile::new_in(tmpdir.path()));
+        check!(temp.write_all(b"kept"));
+        let (file, path) = temp.keep();
+        drop(file);
+        assert_eq!(check!(fs::read(&path)), b"kept");
+        check!(fs::remove_file(&path));
+
+        let temp = check!(fs::NamedTempFile::new_in(tmpdir.path()));
+        let path = temp.path().to_path_buf();
+        check!(temp.close());
+        assert!(!path.exists());
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 0);
+    }
+
+    #[test]
+    fn temp_dir_drop_persist_close() {
+        let tmpdir = tmpdir();
+
+        let dir = check!(fs::TempDir::new_in(tmpdir.path()));
+        let path = dir.path().to_path_buf();
+        assert!(path.is_dir());
+        check!(fs::create_dir(path.join("sub")));
+        check!(fs::write(path.join("sub").join("file"), b"x"));
+        drop(dir);
+        assert!(!path.exists());
+
+        let dir = check!(fs::TempDir::new_in(tmpdir.path()));
+        check!(fs::write(dir.path().join("file"), b"x"));
+        let path = dir.persist();
+        assert_eq!(check!(fs::read(path.join("file"))), b"x");
+        check!(fs::remove_dir_all(&path));
+
+        let dir = check!(fs::TempDir::new_in(tmpdir.path()));
+        let path = dir.path().to_path_buf();
+        check!(fs::write(path.join("file"), b"x"));
+        check!(dir.close());
+        assert!(!path.exists());
+        assert_eq!(check!(fs::read_dir(tmpdir.path())).count(), 0);
+
+        let dir = check!(fs::TempDir::new());
+        assert!(dir.path().is_dir());
+    }
+
+    #[test]
+    fn named_temp_file_persist() {
+        let tmpdir = tmpdir();
+        let mut temp = check!(fs::NamedTempFile::new_in(tmpdir.path()));
+        check!(temp.write_all(b"kept"));
+        let temp_path = temp.path().to_path_buf();
+
+        // A failed rename hands the file back, still in place.
+        let err = temp.persist(tmpdir.join("missing").join("file")).unwrap_err();
+        assert_eq!(err.error.kind(), ErrorKind::NotFound);
+        let temp = err.file;
+        assert_eq!(temp.path(), &*temp_path);
+        assert_eq!(check!(fs::read(&temp_path)), b"kept");
+
+        let path = tmpdir.join("persisted");
+        let mut file = check!(temp.persist(&path));
+        check!(file.write_all(b"!"));
+        drop(file);
+        assert!(!temp_path.exists());
+        assert_eq!(check!(fs::read(&path)), b"kept!");
+
+        // Dropping the error removes the file like dropping it would.
+        let temp = check!(fs::NamedTempFile::new_in(tmpdir.path()));
+        let temp_path = temp.path().to_path_buf();
+        drop(temp.persist(tmpdir.join("missing").join("file")));
+        assert!(!temp_path.exists());
+    }
+
+    #[test]
+    fn file_lock_defaults() {
+        use io;
+        use path::Path;
+        use traits::{File as FileT, Std};
+
+        // Backend files that only have the default locking methods.
+        macro_rules! lockless_file {
+            ($name:ident, $noop:expr) => {
+                #[derive(Debug)]
+                struct $name;
+
+                impl<STD: Std> FileT<STD> for $name {
+                    const NOOP_LOCKING: bool = $noop;
+
+                    fn open(_: &Path<STD>, _: &STD::OpenOptions) -> io::Result<STD::File, STD> {
+                        unimplemented!()
+                    }
+                    fn read(&self, _: &mut [u8]) -> io::Result<usize, STD> { unimplemented!() }
+                    fn write(&self, _: &[u8]) -> io::Result<usize, STD> { unimplemented!() }
+                    fn flush(&self) -> io::Result<(), STD> { unimplemented!() }
+                    fn seek(&self, _: SeekFrom) -> io::Result<u64, STD> { unimplemented!() }
+                    fn fsync(&self) -> io::Result<(), STD> { unimplemented!() }
+                    fn datasync(&self) -> io::Result<(), STD> { unimplemented!() }
+                    fn truncate(&self, _: u64) -> io::Result<(), STD> { unimplemented!() }
+                    fn file_attr(&self) -> io::Result<STD::FileAttr, STD> { unimplemented!() }
+                    fn duplicate(&self) -> io::Result<Self, STD> { unimplemented!() }
+                    fn set_permissions(&self, _: STD::Permissions) -> io::Result<(), STD> {
+                        unimplemented!()
+                    }
+                }
+            }
+        }
+        lockless_file!(Unsupported, false);
+        lockless_file!(Noop, true);
+
+        let f = Unsupported;
+        assert_eq!(FileT::lock_shared(&f).unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(FileT::lock_exclusive(&f).unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(FileT::try_lock_shared(&f).unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(FileT::try_lock_exclusive(&f).unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(FileT::unlock(&f).unwrap_err().kind(), ErrorKind::Unsupported);
+
+        let f = Noop;
+        check!(FileT::lock_shared(&f));
+        check!(FileT::lock_exclusive(&f));
+        assert!(check!(FileT::try_lock_shared(&f)));
+        assert!(check!(FileT::try_lock_exclusive(&f)));
+        check!(FileT::unlock(&f));
+    }
+
+    #[test]
+    fn file_try_lock_shared_exclusive() {
+        let tmpdir = tmpdir();
+        let path = tmpdir.join("lock");
+        let a = check!(File::create(&path));
+        let b = check!(File::open(&path));
+
+        match a.lock_shared() {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == ErrorKind::Unsupported => return,
+            Err(e) => panic!("{}", e),
+        }
+        // Shared locks coexist, but keep out exclusive ones.
+        assert!(check!(b.try_lock_shared()));
+        check!(b.unlock());
+        assert!(!check!(b.try_lock_exclusive()));
+        check!(a.unlock());
+
+        check!(a.lock_exclusive());
+        assert!(!check!(b.try_lock_shared()));
+        assert!(!check!(b.try_lock_exclusive()));
+        check!(a.unlock());
+        assert!(check!(b.try_lock_exclusive()));
+        assert!(!check!(a.try_lock_shared()));
+        check!(b.unlock());
+
+        // Closing the handle releases its lock.
+        check!(a.lock_exclusive());
+        drop(a);
+        assert!(check!(b.try_lock_exclusive()));
+    }
+
     #[test]
     #[cfg(unix)]
     fn set_get_unix_permissions() {
@@ -0,0 +1,268 @@
+// Copyright 2017 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Memory allocation APIs backed by the platform.
+//!
+//! Two global allocators are provided:
+//!
+//! * [`System`] forwards every request to the `alloc`/`dealloc`/`realloc`
+//!   hooks of the platform's [`Std`] implementation.
+//! * [`PageHeap`] is a general-purpose allocator that only needs the page
+//!   level `map_pages`/`unmap_pages` hooks, so a new backend gets a working
+//!   heap without writing an allocator of its own.
+//!
+//! Either can be installed as the global allocator:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static HEAP: PageHeap<MyPlatform> = PageHeap::new();
+//! ```
+//!
+//! [`System`]: struct.System.html
+//! [`PageHeap`]: struct.PageHeap.html
+//! [`Std`]: ../traits/trait.Std.html
+
+use traits::Std;
+use marker::PhantomData;
+
+use alloc::allocator::{GlobalAlloc, Layout};
+use cell::UnsafeCell;
+use cmp;
+use fmt;
+use ptr;
+use sys_common::mutex::Mutex;
+
+/// The allocator provided by the platform.
+///
+/// All calls are forwarded to [`Std::alloc`], [`Std::dealloc`] and
+/// [`Std::realloc`]. Unless the platform overrides them, these map whole
+/// pages for every allocation, which is correct but wasteful; consider
+/// [`PageHeap`] for platforms without a native allocator.
+///
+/// [`Std::alloc`]: ../traits/trait.Std.html#method.alloc
+/// [`Std::dealloc`]: ../traits/trait.Std.html#method.dealloc
+/// [`Std::realloc`]: ../traits/trait.Std.html#method.realloc
+/// [`PageHeap`]: struct.PageHeap.html
+pub struct System<STD: Std> {
+    _std: PhantomData<STD>,
+}
+
+impl<STD: Std> System<STD> {
+    /// Creates a handle to the platform allocator.
+    pub const fn new() -> System<STD> {
+        System { _std: PhantomData }
+    }
+}
+
+unsafe impl<STD: Std> GlobalAlloc for System<STD> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        STD::alloc(layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        STD::dealloc(ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        STD::realloc(ptr, layout, new_size)
+    }
+}
+
+impl<STD: Std> fmt::Debug for System<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("System { .. }")
+    }
+}
+
+/// Allocates `layout` by mapping whole pages through [`Std::map_pages`].
+///
+/// This is the default implementation of [`Std::alloc`]. Alignments larger
+/// than the platform page size are not supported and yield a null pointer.
+///
+/// [`Std::map_pages`]: ../traits/trait.Std.html#method.map_pages
+/// [`Std::alloc`]: ../traits/trait.Std.html#method.alloc
+pub unsafe fn map_alloc<STD: Std>(layout: Layout) -> *mut u8 {
+    let page_size = STD::page_size();
+    if layout.align() > page_size {
+        return ptr::null_mut();
+    }
+    match round_up(cmp::max(layout.size(), 1), page_size) {
+        Some(len) => STD::map_pages(len),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Releases memory obtained from [`map_alloc`].
+///
+/// [`map_alloc`]: fn.map_alloc.html
+pub unsafe fn map_dealloc<STD: Std>(ptr: *mut u8, layout: Layout) {
+    let len = round_up(cmp::max(layout.size(), 1), STD::page_size())
+        .expect("layout size overflowed while deallocating");
+    STD::unmap_pages(ptr, len)
+}
+
+/// Resizes a block by allocating a new one, copying the contents over and
+/// releasing the old one.
+///
+/// This is the default implementation of [`Std::realloc`].
+///
+/// [`Std::realloc`]: ../traits/trait.Std.html#method.realloc
+pub unsafe fn realloc_fallback<STD: Std>(ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+    let new_ptr = STD::alloc(new_layout);
+    if !new_ptr.is_null() {
+        ptr::copy_nonoverlapping(ptr, new_ptr, cmp::min(layout.size(), new_size));
+        STD::dealloc(ptr, layout);
+    }
+    new_ptr
+}
+
+// Blocks are handed out in power-of-two size classes starting at
+// `MIN_BLOCK`. Anything larger than half a page is mapped directly.
+const MIN_BLOCK: usize = 16;
+const NUM_CLASSES: usize = 16;
+
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+/// A general-purpose allocator built on top of [`Std::map_pages`].
+///
+/// Small requests are served from segregated free lists of power-of-two
+/// sized blocks which are carved out of whole pages. Requests larger than
+/// half a page go straight to [`Std::map_pages`] and are unmapped again
+/// when freed. Pages used for small blocks are kept for reuse and are never
+/// returned to the platform.
+///
+/// All state is protected by a platform [`Mutex`], so a `PageHeap` is meant
+/// to live in a `static`.
+///
+/// [`Std::map_pages`]: ../traits/trait.Std.html#method.map_pages
+/// [`Mutex`]: ../sys_common/mutex/struct.Mutex.html
+pub struct PageHeap<STD: Std> {
+    lock: Mutex<STD>,
+    free: UnsafeCell<[*mut FreeBlock; NUM_CLASSES]>,
+}
+
+unsafe impl<STD: Std> Sync for PageHeap<STD> {}
+unsafe impl<STD: Std> Send for PageHeap<STD> {}
+
+impl<STD: Std> PageHeap<STD> {
+    /// Creates an empty heap. No memory is mapped until the first
+    /// allocation.
+    pub const fn new() -> PageHeap<STD> {
+        PageHeap {
+            lock: Mutex::new(),
+            free: UnsafeCell::new([0 as *mut FreeBlock; NUM_CLASSES]),
+        }
+    }
+
+    // Returns the index of the size class serving `layout`, or `None` if
+    // the request has to be mapped directly.
+    fn class_of(layout: &Layout) -> Option<usize> {
+        let size = cmp::max(cmp::max(layout.size(), layout.align()), MIN_BLOCK);
+        let block = size.checked_next_power_of_two()?;
+        if block > STD::page_size() / 2 {
+            return None;
+        }
+        let class = (block.trailing_zeros() - MIN_BLOCK.trailing_zeros()) as usize;
+        if class < NUM_CLASSES { Some(class) } else { None }
+    }
+
+    // Maps a fresh page and threads all blocks of `class` onto its free
+    // list. Must be called with `self.lock` held.
+    unsafe fn refill(&self, class: usize) -> bool {
+        let page_size = STD::page_size();
+        let page = STD::map_pages(page_size);
+        if page.is_null() {
+            return false;
+        }
+        let block = MIN_BLOCK << class;
+        let free = &mut (*self.free.get())[class];
+        let mut offset = page_size;
+        while offset >= block {
+            offset -= block;
+            let b = page.offset(offset as isize) as *mut FreeBlock;
+            (*b).next = *free;
+            *free = b;
+        }
+        true
+    }
+}
+
+unsafe impl<STD: Std> GlobalAlloc for PageHeap<STD> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = match Self::class_of(&layout) {
+            Some(class) => class,
+            None => return map_alloc::<STD>(layout),
+        };
+
+        self.lock.lock();
+        if (*self.free.get())[class].is_null() && !self.refill(class) {
+            self.lock.unlock();
+            return ptr::null_mut();
+        }
+        let free = &mut (*self.free.get())[class];
+        let block = *free;
+        *free = (*block).next;
+        self.lock.unlock();
+        block as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = match Self::class_of(&layout) {
+            Some(class) => class,
+            None => return map_dealloc::<STD>(ptr, layout),
+        };
+
+        self.lock.lock();
+        let free = &mut (*self.free.get())[class];
+        let block = ptr as *mut FreeBlock;
+        (*block).next = *free;
+        *free = block;
+        self.lock.unlock();
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        match (Self::class_of(&layout), Self::class_of(&new_layout)) {
+            // Still fits the same block.
+            (Some(old), Some(new)) if old == new => return ptr,
+            // Page-mapped blocks keep their rounded-up capacity.
+            (None, None) => {
+                let page_size = STD::page_size();
+                if round_up(layout.size(), page_size) == round_up(new_size, page_size) {
+                    return ptr;
+                }
+            }
+            _ => {}
+        }
+
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, cmp::min(layout.size(), new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+impl<STD: Std> fmt::Debug for PageHeap<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("PageHeap { .. }")
+    }
+}
+
+fn round_up(size: usize, align: usize) -> Option<usize> {
+    Some(size.checked_add(align - 1)? & !(align - 1))
+}
@@ -0,0 +1,365 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Asynchronous counterparts of `Read`, `Write` and `Seek`.
+//!
+//! The traits are poll-based: an operation that can't complete right away
+//! returns `Poll::Pending` after arranging for the task's waker to be woken
+//! once it can make progress. The `*Ext` traits wrap them into
+//! [`task::Future`]s.
+//!
+//! [`task::Future`]: ../task/trait.Future.html
+
+use prelude::*;
+use traits::Std;
+
+use cmp;
+use io::{Chain, ErrorKind, Result, SeekFrom, Take};
+use marker::PhantomData;
+use mem;
+use ptr;
+use task::{Context, Future, Poll};
+
+/// Reads bytes asynchronously.
+pub trait AsyncRead<STD: Std> {
+    /// Attempts to read into `buf`, returning how many bytes were read.
+    ///
+    /// Returns `Poll::Pending` and schedules the current task to be woken if
+    /// no data is available yet. `Ok(0)` signals end of file, just like
+    /// `Read::read`.
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8])
+                 -> Poll<Result<usize, STD>>;
+}
+
+/// Writes bytes asynchronously.
+pub trait AsyncWrite<STD: Std> {
+    /// Attempts to write from `buf`, returning how many bytes were written.
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8])
+                  -> Poll<Result<usize, STD>>;
+
+    /// Attempts to flush buffered data out to its destination.
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), STD>>;
+}
+
+/// Seeks asynchronously.
+pub trait AsyncSeek<STD: Std> {
+    /// Attempts to seek to `pos`, returning the new position from the start.
+    fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom)
+                 -> Poll<Result<u64, STD>>;
+}
+
+macro_rules! deref_async_read {
+    () => {
+        fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8])
+                     -> Poll<Result<usize, STD>> {
+            (**self).poll_read(cx, buf)
+        }
+    }
+}
+
+macro_rules! deref_async_write {
+    () => {
+        fn poll_write(&mut self, cx: &mut Context, buf: &[u8])
+                      -> Poll<Result<usize, STD>> {
+            (**self).poll_write(cx, buf)
+        }
+
+        fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), STD>> {
+            (**self).poll_flush(cx)
+        }
+    }
+}
+
+macro_rules! deref_async_seek {
+    () => {
+        fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom)
+                     -> Poll<Result<u64, STD>> {
+            (**self).poll_seek(cx, pos)
+        }
+    }
+}
+
+impl<'a, T: AsyncRead<STD> + ?Sized, STD: Std> AsyncRead<STD> for &'a mut T {
+    deref_async_read!();
+}
+
+impl<T: AsyncRead<STD> + ?Sized, STD: Std> AsyncRead<STD> for Box<T> {
+    deref_async_read!();
+}
+
+impl<'a, T: AsyncWrite<STD> + ?Sized, STD: Std> AsyncWrite<STD> for &'a mut T {
+    deref_async_write!();
+}
+
+impl<T: AsyncWrite<STD> + ?Sized, STD: Std> AsyncWrite<STD> for Box<T> {
+    deref_async_write!();
+}
+
+impl<'a, T: AsyncSeek<STD> + ?Sized, STD: Std> AsyncSeek<STD> for &'a mut T {
+    deref_async_seek!();
+}
+
+impl<T: AsyncSeek<STD> + ?Sized, STD: Std> AsyncSeek<STD> for Box<T> {
+    deref_async_seek!();
+}
+
+impl<T: AsyncRead<STD>, STD: Std> AsyncRead<STD> for Take<T> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8])
+                 -> Poll<Result<usize, STD>> {
+        // Don't call into inner reader at all at EOF because it may still block
+        if self.limit == 0 {
+            return Poll::Ready(Ok(0));
+        }
+
+        let max = cmp::min(buf.len() as u64, self.limit) as usize;
+        match self.inner.poll_read(cx, &mut buf[..max]) {
+            Poll::Ready(Ok(n)) => {
+                self.limit -= n as u64;
+                Poll::Ready(Ok(n))
+            }
+            other => other,
+        }
+    }
+}
+
+impl<T: AsyncRead<STD>, U: AsyncRead<STD>, STD: Std> AsyncRead<STD> for Chain<T, U> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8])
+                 -> Poll<Result<usize, STD>> {
+        if !self.done_first {
+            match self.first.poll_read(cx, buf) {
+                Poll::Ready(Ok(0)) if buf.len() != 0 => self.done_first = true,
+                other => return other,
+            }
+        }
+        self.second.poll_read(cx, buf)
+    }
+}
+
+/// Future-returning helpers for [`AsyncRead`] types.
+pub trait AsyncReadExt<STD: Std>: AsyncRead<STD> {
+    /// Reads some bytes into `buf`, like `Read::read`.
+    fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> ReadFuture<'a, Self, STD> {
+        ReadFuture { reader: self, buf: buf, _std: PhantomData }
+    }
+
+    /// Reads until end of file, appending to `buf`, like `Read::read_to_end`.
+    fn read_to_end<'a>(&'a mut self, buf: &'a mut Vec<u8>) -> ReadToEnd<'a, Self, STD> {
+        let start_len = buf.len();
+        ReadToEnd {
+            reader: self,
+            buf: buf,
+            start_len: start_len,
+            initialized: start_len,
+            _std: PhantomData,
+        }
+    }
+
+    /// Creates an adaptor reading at most `limit` bytes, like `Read::take`.
+    fn take(self, limit: u64) -> Take<Self> where Self: Sized {
+        Take { inner: self, limit: limit }
+    }
+
+    /// Creates an adaptor reading `next` once `self` hits end of file, like
+    /// `Read::chain`.
+    fn chain<R: AsyncRead<STD>>(self, next: R) -> Chain<Self, R> where Self: Sized {
+        Chain { first: self, second: next, done_first: false }
+    }
+}
+
+impl<R: AsyncRead<STD> + ?Sized, STD: Std> AsyncReadExt<STD> for R {}
+
+/// Future-returning helpers for [`AsyncWrite`] types.
+pub trait AsyncWriteExt<STD: Std>: AsyncWrite<STD> {
+    /// Writes some bytes from `buf`, like `Write::write`.
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> WriteFuture<'a, Self, STD> {
+        WriteFuture { writer: self, buf: buf, _std: PhantomData }
+    }
+
+    /// Writes all of `buf`, like `Write::write_all`.
+    fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> WriteAll<'a, Self, STD> {
+        WriteAll { writer: self, buf: buf, _std: PhantomData }
+    }
+
+    /// Flushes buffered data, like `Write::flush`.
+    fn flush(&mut self) -> Flush<Self, STD> {
+        Flush { writer: self, _std: PhantomData }
+    }
+}
+
+impl<W: AsyncWrite<STD> + ?Sized, STD: Std> AsyncWriteExt<STD> for W {}
+
+/// Future-returning helpers for [`AsyncSeek`] types.
+pub trait AsyncSeekExt<STD: Std>: AsyncSeek<STD> {
+    /// Seeks to `pos`, like `Seek::seek`.
+    fn seek(&mut self, pos: SeekFrom) -> SeekFuture<Self, STD> {
+        SeekFuture { seeker: self, pos: pos, _std: PhantomData }
+    }
+}
+
+impl<S: AsyncSeek<STD> + ?Sized, STD: Std> AsyncSeekExt<STD> for S {}
+
+/// Future for [`AsyncReadExt::read`].
+#[derive(Debug)]
+pub struct ReadFuture<'a, R: ?Sized + 'a, STD> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+    _std: PhantomData<STD>,
+}
+
+impl<'a, R: AsyncRead<STD> + ?Sized, STD: Std> Future for ReadFuture<'a, R, STD> {
+    type Output = Result<usize, STD>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<usize, STD>> {
+        self.reader.poll_read(cx, self.buf)
+    }
+}
+
+/// Future for [`AsyncReadExt::read_to_end`].
+#[derive(Debug)]
+pub struct ReadToEnd<'a, R: ?Sized + 'a, STD> {
+    reader: &'a mut R,
+    buf: &'a mut Vec<u8>,
+    start_len: usize,
+    // How much of `buf`'s allocation is known to be initialized, including
+    // spare capacity zeroed by an earlier poll.
+    initialized: usize,
+    _std: PhantomData<STD>,
+}
+
+impl<'a, R: AsyncRead<STD> + ?Sized, STD: Std> Future for ReadToEnd<'a, R, STD> {
+    type Output = Result<usize, STD>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<usize, STD>> {
+        let this = self;
+        loop {
+            let len = this.buf.len();
+            if len == this.buf.capacity() {
+                this.buf.reserve(32);
+                // The allocation may have moved, leaving only the contents
+                // initialized.
+                this.initialized = len;
+            }
+
+            // `poll_read` may be called again with the same buffer if it
+            // returns `Pending`, so the spare capacity can't be handed out
+            // uninitialized. Zero it once and keep track of that, rather
+            // than on every poll.
+            let capacity = this.buf.capacity();
+            unsafe {
+                if this.initialized < capacity {
+                    ptr::write_bytes(this.buf.as_mut_ptr().offset(this.initialized as isize),
+                                     0, capacity - this.initialized);
+                    this.initialized = capacity;
+                }
+                this.buf.set_len(capacity);
+            }
+
+            let ret = this.reader.poll_read(cx, &mut this.buf[len..]);
+            match ret {
+                Poll::Ready(Ok(0)) => {
+                    this.buf.truncate(len);
+                    return Poll::Ready(Ok(len - this.start_len));
+                }
+                Poll::Ready(Ok(n)) => this.buf.truncate(len + n),
+                Poll::Ready(Err(ref e)) if e.kind() == ErrorKind::Interrupted => {
+                    this.buf.truncate(len);
+                }
+                Poll::Ready(Err(e)) => {
+                    this.buf.truncate(len);
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Pending => {
+                    this.buf.truncate(len);
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+/// Future for [`AsyncWriteExt::write`].
+#[derive(Debug)]
+pub struct WriteFuture<'a, W: ?Sized + 'a, STD> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+    _std: PhantomData<STD>,
+}
+
+impl<'a, W: AsyncWrite<STD> + ?Sized, STD: Std> Future for WriteFuture<'a, W, STD> {
+    type Output = Result<usize, STD>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<usize, STD>> {
+        self.writer.poll_write(cx, self.buf)
+    }
+}
+
+/// Future for [`AsyncWriteExt::write_all`].
+#[derive(Debug)]
+pub struct WriteAll<'a, W: ?Sized + 'a, STD> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+    _std: PhantomData<STD>,
+}
+
+impl<'a, W: AsyncWrite<STD> + ?Sized, STD: Std> Future for WriteAll<'a, W, STD> {
+    type Output = Result<(), STD>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<(), STD>> {
+        let this = self;
+        while !this.buf.is_empty() {
+            match this.writer.poll_write(cx, this.buf) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(const_error!(ErrorKind::WriteZero,
+                                                        "failed to write whole buffer")));
+                }
+                Poll::Ready(Ok(n)) => {
+                    let buf = mem::replace(&mut this.buf, &[]);
+                    this.buf = &buf[n..];
+                }
+                Poll::Ready(Err(ref e)) if e.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Future for [`AsyncWriteExt::flush`].
+#[derive(Debug)]
+pub struct Flush<'a, W: ?Sized + 'a, STD> {
+    writer: &'a mut W,
+    _std: PhantomData<STD>,
+}
+
+impl<'a, W: AsyncWrite<STD> + ?Sized, STD: Std> Future for Flush<'a, W, STD> {
+    type Output = Result<(), STD>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<(), STD>> {
+        self.writer.poll_flush(cx)
+    }
+}
+
+/// Future for [`AsyncSeekExt::seek`].
+#[derive(Debug)]
+pub struct SeekFuture<'a, S: ?Sized + 'a, STD> {
+    seeker: &'a mut S,
+    pos: SeekFrom,
+    _std: PhantomData<STD>,
+}
+
+impl<'a, S: AsyncSeek<STD> + ?Sized, STD: Std> Future for SeekFuture<'a, S, STD> {
+    type Output = Result<u64, STD>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<u64, STD>> {
+        let pos = self.pos;
+        self.seeker.poll_seek(cx, pos)
+    }
+}
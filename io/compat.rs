@@ -0,0 +1,222 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Adapters between this crate's I/O traits and the host's `std::io`.
+//!
+//! [`ToStd`] lets a reader or writer of this crate be handed to code that
+//! expects `std::io::Read`, `Write`, `Seek` or `BufRead`, and [`FromStd`]
+//! does the opposite. Errors are converted with the `From` impls between
+//! `io::Error` and `std::io::Error`, so OS error codes and custom payloads
+//! survive the trip.
+//!
+//! Only available with the `std` feature.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use fs::File;
+//! use io::compat::ToStd;
+//!
+//! # fn foo() -> ::std::io::Result<()> {
+//! let file = File::<MyPlatform>::open("foo.txt")?;
+//! let mut reader = ToStd::new(file);
+//! let mut contents = Vec::new();
+//! ::std::io::Read::read_to_end(&mut reader, &mut contents)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use prelude::*;
+use traits::Std;
+
+use fmt;
+use io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use marker::PhantomData;
+
+/// Implements the host's `std::io` traits for a reader or writer of this
+/// crate.
+pub struct ToStd<T, STD: Std> {
+    inner: T,
+    _std: PhantomData<STD>,
+}
+
+impl<T, STD: Std> ToStd<T, STD> {
+    /// Wraps `inner`.
+    pub fn new(inner: T) -> ToStd<T, STD> {
+        ToStd {
+            inner: inner,
+            _std: PhantomData,
+        }
+    }
+
+    /// Gets a reference to the wrapped value.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the wrapped value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps this adapter, returning the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: fmt::Debug, STD: Std> fmt::Debug for ToStd<T, STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ToStd").field(&self.inner).finish()
+    }
+}
+
+impl<T: Read<STD>, STD: Std> ::std::io::Read for ToStd<T, STD>
+    where STD::OsError: Into<i32>
+{
+    fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+        Ok(self.inner.read(buf)?)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> ::std::io::Result<usize> {
+        Ok(self.inner.read_to_end(buf)?)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> ::std::io::Result<()> {
+        Ok(self.inner.read_exact(buf)?)
+    }
+}
+
+impl<T: Write<STD>, STD: Std> ::std::io::Write for ToStd<T, STD>
+    where STD::OsError: Into<i32>
+{
+    fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+        Ok(self.inner.write(buf)?)
+    }
+
+    fn flush(&mut self) -> ::std::io::Result<()> {
+        Ok(self.inner.flush()?)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> ::std::io::Result<()> {
+        Ok(self.inner.write_all(buf)?)
+    }
+}
+
+impl<T: Seek<STD>, STD: Std> ::std::io::Seek for ToStd<T, STD>
+    where STD::OsError: Into<i32>
+{
+    fn seek(&mut self, pos: ::std::io::SeekFrom) -> ::std::io::Result<u64> {
+        let pos = match pos {
+            ::std::io::SeekFrom::Start(n) => SeekFrom::Start(n),
+            ::std::io::SeekFrom::End(n) => SeekFrom::End(n),
+            ::std::io::SeekFrom::Current(n) => SeekFrom::Current(n),
+        };
+        Ok(self.inner.seek(pos)?)
+    }
+}
+
+impl<T: BufRead<STD>, STD: Std> ::std::io::BufRead for ToStd<T, STD>
+    where STD::OsError: Into<i32>
+{
+    fn fill_buf(&mut self) -> ::std::io::Result<&[u8]> {
+        Ok(self.inner.fill_buf()?)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+/// Implements this crate's I/O traits for a reader or writer of the host's
+/// `std::io`.
+#[derive(Debug)]
+pub struct FromStd<T> {
+    inner: T,
+}
+
+impl<T> FromStd<T> {
+    /// Wraps `inner`.
+    pub fn new(inner: T) -> FromStd<T> {
+        FromStd { inner: inner }
+    }
+
+    /// Gets a reference to the wrapped value.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the wrapped value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps this adapter, returning the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ::std::io::Read, STD: Std> Read<STD> for FromStd<T>
+    where STD::OsError: From<i32>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize, STD> {
+        Ok(self.inner.read(buf)?)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize, STD> {
+        Ok(self.inner.read_to_end(buf)?)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<(), STD> {
+        Ok(self.inner.read_exact(buf)?)
+    }
+}
+
+impl<T: ::std::io::Write, STD: Std> Write<STD> for FromStd<T>
+    where STD::OsError: From<i32>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize, STD> {
+        Ok(self.inner.write(buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<(), STD> {
+        Ok(self.inner.flush()?)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<(), STD> {
+        Ok(self.inner.write_all(buf)?)
+    }
+}
+
+impl<T: ::std::io::Seek, STD: Std> Seek<STD> for FromStd<T>
+    where STD::OsError: From<i32>
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64, STD> {
+        let pos = match pos {
+            SeekFrom::Start(n) => ::std::io::SeekFrom::Start(n),
+            SeekFrom::End(n) => ::std::io::SeekFrom::End(n),
+            SeekFrom::Current(n) => ::std::io::SeekFrom::Current(n),
+        };
+        Ok(self.inner.seek(pos)?)
+    }
+}
+
+impl<T: ::std::io::BufRead, STD: Std> BufRead<STD> for FromStd<T>
+    where STD::OsError: From<i32>
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8], STD> {
+        Ok(self.inner.fill_buf()?)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
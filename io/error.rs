@@ -64,7 +64,7 @@ pub type Result<T, STD> = result::Result<T, Error<STD>>;
 /// [`Write`]: ../io/trait.Write.html
 /// [`Seek`]: ../io/trait.Seek.html
 /// [`ErrorKind`]: enum.ErrorKind.html
-pub struct Error<STD> {
+pub struct Error<STD: Std> {
     repr: Repr<STD>,
     _std: PhantomData<STD>,
 }
@@ -75,10 +75,12 @@ impl<STD: Std> fmt::Debug for Error<STD> {
     }
 }
 
-enum Repr<STD> {
-    Os(i32),
+enum Repr<STD: Std> {
+    Os(STD::OsError),
     Simple(ErrorKind),
+    SimpleMessage(&'static SimpleMessage),
     Custom(Box<Custom>),
+    Context(Box<Context<STD>>),
     __Std(PhantomData<STD>),
 }
 
@@ -88,6 +90,54 @@ struct Custom {
     error: Box<error::Error+Send+Sync>,
 }
 
+/// The static payload of an error built by [`const_error!`].
+///
+/// [`const_error!`]: ../macro.const_error.html
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct SimpleMessage {
+    kind: ErrorKind,
+    message: &'static str,
+}
+
+impl SimpleMessage {
+    #[doc(hidden)]
+    pub const fn new(kind: ErrorKind, message: &'static str) -> SimpleMessage {
+        SimpleMessage { kind: kind, message: message }
+    }
+}
+
+/// Creates an `io::Error` from a kind and a string literal without
+/// allocating.
+///
+/// The message is stored in a constant, so this is usable where allocation
+/// may fail, such as when reporting that it did.
+///
+/// # Examples
+///
+/// ```ignore
+/// use io::{self, ErrorKind};
+///
+/// let err: io::Error<MyPlatform> = const_error!(ErrorKind::NotFound, "no such widget");
+/// assert_eq!(err.kind(), ErrorKind::NotFound);
+/// assert_eq!(err.to_string(), "no such widget");
+/// ```
+#[macro_export]
+macro_rules! const_error {
+    ($kind:expr, $message:expr) => {
+        $crate::io::Error::from_static_message({
+            const MESSAGE: $crate::io::SimpleMessage =
+                $crate::io::SimpleMessage::new($kind, $message);
+            &MESSAGE
+        })
+    };
+}
+
+struct Context<STD: Std> {
+    context: String,
+    error: Error<STD>,
+}
+
 /// A list specifying general categories of I/O error.
 ///
 /// This list is intended to grow over time and it is not recommended to
@@ -183,6 +233,50 @@ pub enum ErrorKind {
     #[stable(feature = "read_exact", since = "1.6.0")]
     UnexpectedEof,
 
+    /// A filesystem object is, unexpectedly, not a directory.
+    ///
+    /// For example, a path component that should have been a directory was
+    /// a file.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    NotADirectory,
+    /// A filesystem object is, unexpectedly, a directory.
+    ///
+    /// A directory was given where a non-directory was expected.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    IsADirectory,
+    /// A non-empty directory was given where an empty one was expected.
+    ///
+    /// For example, `fs::remove_dir` on a directory that still has entries.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    DirectoryNotEmpty,
+    /// The filesystem or storage medium is read-only, but a write operation
+    /// was attempted.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    ReadOnlyFilesystem,
+    /// The underlying storage is full.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    StorageFull,
+    /// Cross-device or cross-filesystem (hard) link or rename.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    CrossesDevices,
+    /// Loop in the filesystem or IO subsystem, often too many levels of
+    /// symbolic links.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    FilesystemLoop,
+    /// A filename was invalid, usually because it was too long.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    InvalidFilename,
+    /// This operation is unsupported on this platform.
+    ///
+    /// The operation will never succeed here, so there is no point in
+    /// retrying it.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    Unsupported,
+    /// An operation could not be completed because it failed to allocate
+    /// enough memory.
+    #[unstable(feature = "io_error_more", issue = "0")]
+    OutOfMemory,
+
     /// A marker variant that tells the compiler that users of this enum cannot
     /// match it exhaustively.
     #[unstable(feature = "io_error_internals",
@@ -194,7 +288,9 @@ pub enum ErrorKind {
 }
 
 impl ErrorKind {
-    fn as_str(&self) -> &'static str {
+    /// Returns a short description of the error kind, the same text `Display`
+    /// shows for errors that carry nothing but a kind.
+    pub fn as_str(&self) -> &'static str {
         match *self {
             ErrorKind::NotFound => "entity not found",
             ErrorKind::PermissionDenied => "permission denied",
@@ -214,6 +310,16 @@ impl ErrorKind {
             ErrorKind::Interrupted => "operation interrupted",
             ErrorKind::Other => "other os error",
             ErrorKind::UnexpectedEof => "unexpected end of file",
+            ErrorKind::NotADirectory => "not a directory",
+            ErrorKind::IsADirectory => "is a directory",
+            ErrorKind::DirectoryNotEmpty => "directory not empty",
+            ErrorKind::ReadOnlyFilesystem => "read-only filesystem or storage medium",
+            ErrorKind::StorageFull => "no storage space",
+            ErrorKind::CrossesDevices => "cross-device link or rename",
+            ErrorKind::FilesystemLoop => "filesystem loop or indirection limit",
+            ErrorKind::InvalidFilename => "invalid filename",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::OutOfMemory => "out of memory",
             ErrorKind::__Nonexhaustive => unreachable!()
         }
     }
@@ -258,6 +364,19 @@ impl<STD: Std> Error<STD> {
         Self::_new(kind, error.into())
     }
 
+    /// Creates an error from a static message without allocating.
+    ///
+    /// Use [`const_error!`] rather than calling this directly.
+    ///
+    /// [`const_error!`]: ../macro.const_error.html
+    #[doc(hidden)]
+    pub const fn from_static_message(msg: &'static SimpleMessage) -> Error<STD> {
+        Error {
+            repr: Repr::SimpleMessage(msg),
+            _std: PhantomData,
+        }
+    }
+
     fn _new(kind: ErrorKind, error: Box<error::Error+Send+Sync>) -> Error<STD> {
         Error {
             repr: Repr::Custom(Box::new(Custom {
@@ -268,6 +387,80 @@ impl<STD: Std> Error<STD> {
         }
     }
 
+    /// Attaches a description of what was being done when this error
+    /// happened, for example which file was being opened.
+    ///
+    /// The returned error keeps the `kind` and `raw_os_error` of `self`, and
+    /// shows the context in front of the original message.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use io::{Error, ErrorKind};
+    ///
+    /// let err = Error::from(ErrorKind::NotFound).with_context("failed to open `foo.txt`");
+    /// assert_eq!(err.kind(), ErrorKind::NotFound);
+    /// assert_eq!(err.to_string(), "failed to open `foo.txt`: entity not found");
+    /// ```
+    pub fn with_context<C: Into<String>>(self, context: C) -> Error<STD> {
+        Error {
+            repr: Repr::Context(Box::new(Context {
+                context: context.into(),
+                error: self,
+            })),
+            _std: PhantomData,
+        }
+    }
+
+    /// Returns the context attached by `with_context`, if any.
+    pub fn context(&self) -> Option<&str> {
+        match self.repr {
+            Repr::Context(ref c) => Some(&c.context),
+            _ => None,
+        }
+    }
+
+    /// Converts this error into an error of another platform.
+    ///
+    /// OS error codes are converted with `From`, and messages, custom
+    /// payloads and context are carried over as they are. This lets a
+    /// backend wrapping another one forward the inner backend's errors.
+    ///
+    /// The kind is always preserved. An OS error whose converted code `B`
+    /// decodes to a different kind than `STD` does is carried over as a
+    /// custom error of the original kind instead, wrapping the original
+    /// error, so its message survives but `raw_os_error` returns `None`.
+    pub fn map_platform<B: Std>(self) -> Error<B>
+        where B::OsError: From<STD::OsError>
+    {
+        if let Repr::Os(code) = self.repr {
+            let kind = STD::decode_error_kind(code);
+            let mapped = B::OsError::from(code);
+            if B::decode_error_kind(mapped) != kind {
+                return Error::_new(kind, Box::new(self));
+            }
+        }
+
+        let repr = match self.repr {
+            Repr::Os(code) => Repr::Os(B::OsError::from(code)),
+            Repr::Simple(kind) => Repr::Simple(kind),
+            Repr::SimpleMessage(m) => Repr::SimpleMessage(m),
+            Repr::Custom(c) => Repr::Custom(c),
+            Repr::Context(c) => {
+                let c = *c;
+                Repr::Context(Box::new(Context {
+                    context: c.context,
+                    error: c.error.map_platform(),
+                }))
+            }
+            Repr::__Std(..) => unreachable!(),
+        };
+        Error {
+            repr: repr,
+            _std: PhantomData,
+        }
+    }
+
     /// Returns an error representing the last OS error which occurred.
     ///
     /// This function reads the value of `errno` for the target platform (e.g.
@@ -288,6 +481,9 @@ impl<STD: Std> Error<STD> {
 
     /// Creates a new instance of an `Error` from a particular OS error code.
     ///
+    /// The code is of the platform's `Std::OsError` type, which is `i32`
+    /// unless the platform defines its own.
+    ///
     /// # Examples
     ///
     /// On Linux:
@@ -312,7 +508,7 @@ impl<STD: Std> Error<STD> {
     /// # }
     /// ```
     #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn from_raw_os_error(code: i32) -> Error<STD> {
+    pub fn from_raw_os_error(code: STD::OsError) -> Error<STD> {
         Error {
             repr: Repr::Os(code),
             _std: PhantomData,
@@ -323,7 +519,8 @@ impl<STD: Std> Error<STD> {
     ///
     /// If this `Error` was constructed via `last_os_error` or
     /// `from_raw_os_error`, then this function will return `Some`, otherwise
-    /// it will return `None`.
+    /// it will return `None`. Errors with context attached by `with_context`
+    /// return the code of the error they wrap.
     ///
     /// # Examples
     ///
@@ -346,11 +543,13 @@ impl<STD: Std> Error<STD> {
     /// }
     /// ```
     #[stable(feature = "rust1", since = "1.0.0")]
-    pub fn raw_os_error(&self) -> Option<i32> {
+    pub fn raw_os_error(&self) -> Option<STD::OsError> {
         match self.repr {
             Repr::Os(i) => Some(i),
             Repr::Custom(..) => None,
             Repr::Simple(..) => None,
+            Repr::SimpleMessage(..) => None,
+            Repr::Context(ref c) => c.error.raw_os_error(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -385,7 +584,9 @@ impl<STD: Std> Error<STD> {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::SimpleMessage(..) => None,
             Repr::Custom(ref c) => Some(&*c.error),
+            Repr::Context(ref c) => c.error.get_ref(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -457,7 +658,9 @@ impl<STD: Std> Error<STD> {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::SimpleMessage(..) => None,
             Repr::Custom(ref mut c) => Some(&mut *c.error),
+            Repr::Context(ref mut c) => c.error.get_mut(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -492,7 +695,9 @@ impl<STD: Std> Error<STD> {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::SimpleMessage(..) => None,
             Repr::Custom(c) => Some(c.error),
+            Repr::Context(c) => c.error.into_inner(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -521,6 +726,8 @@ impl<STD: Std> Error<STD> {
             Repr::Os(code) => STD::decode_error_kind(code),
             Repr::Custom(ref c) => c.kind,
             Repr::Simple(kind) => kind,
+            Repr::SimpleMessage(m) => m.kind,
+            Repr::Context(ref c) => c.error.kind(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -536,6 +743,14 @@ impl<STD: Std> fmt::Debug for Repr<STD> {
                     .field("message", &STD::error_string(code)).finish(),
             Repr::Custom(ref c) => fmt::Debug::fmt(&c, fmt),
             Repr::Simple(kind) => fmt.debug_tuple("Kind").field(&kind).finish(),
+            Repr::SimpleMessage(m) =>
+                fmt.debug_struct("Error")
+                    .field("kind", &m.kind)
+                    .field("message", &m.message).finish(),
+            Repr::Context(ref c) =>
+                fmt.debug_struct("Context")
+                    .field("context", &c.context)
+                    .field("error", &c.error).finish(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -551,6 +766,8 @@ impl<STD: Std> fmt::Display for Error<STD> {
             }
             Repr::Custom(ref c) => c.error.fmt(fmt),
             Repr::Simple(kind) => write!(fmt, "{}", kind.as_str()),
+            Repr::SimpleMessage(m) => fmt.write_str(m.message),
+            Repr::Context(ref c) => write!(fmt, "{}: {}", c.context, c.error),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -561,7 +778,9 @@ impl<STD: Std> error::Error for Error<STD> {
     fn description(&self) -> &str {
         match self.repr {
             Repr::Os(..) | Repr::Simple(..) => self.kind().as_str(),
+            Repr::SimpleMessage(m) => m.message,
             Repr::Custom(ref c) => c.error.description(),
+            Repr::Context(ref c) => c.error.description(),
             Repr::__Std(..) => unreachable!(),
         }
     }
@@ -570,12 +789,190 @@ impl<STD: Std> error::Error for Error<STD> {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::SimpleMessage(..) => None,
             Repr::Custom(ref c) => c.error.cause(),
+            Repr::Context(ref c) => c.error.cause(),
+            Repr::__Std(..) => unreachable!(),
+        }
+    }
+
+    fn source(&self) -> Option<&error::Error> {
+        match self.repr {
+            Repr::Os(..) => None,
+            Repr::Simple(..) => None,
+            Repr::SimpleMessage(..) => None,
+            Repr::Custom(ref c) => Some(&*c.error),
+            Repr::Context(ref c) => c.error.source(),
+            Repr::__Std(..) => unreachable!(),
+        }
+    }
+}
+
+// Conversions to and from the host's `std::io::Error`, for builds that run
+// on top of the standard library.
+
+// Kinds the host's `std::io::ErrorKind` doesn't have on this toolchain map to
+// `Other`.
+#[cfg(feature = "std")]
+fn kind_to_host(kind: ErrorKind) -> ::std::io::ErrorKind {
+    use std::io::ErrorKind as Host;
+
+    match kind {
+        ErrorKind::NotFound => Host::NotFound,
+        ErrorKind::PermissionDenied => Host::PermissionDenied,
+        ErrorKind::ConnectionRefused => Host::ConnectionRefused,
+        ErrorKind::ConnectionReset => Host::ConnectionReset,
+        ErrorKind::ConnectionAborted => Host::ConnectionAborted,
+        ErrorKind::NotConnected => Host::NotConnected,
+        ErrorKind::AddrInUse => Host::AddrInUse,
+        ErrorKind::AddrNotAvailable => Host::AddrNotAvailable,
+        ErrorKind::BrokenPipe => Host::BrokenPipe,
+        ErrorKind::AlreadyExists => Host::AlreadyExists,
+        ErrorKind::WouldBlock => Host::WouldBlock,
+        ErrorKind::InvalidInput => Host::InvalidInput,
+        ErrorKind::InvalidData => Host::InvalidData,
+        ErrorKind::TimedOut => Host::TimedOut,
+        ErrorKind::WriteZero => Host::WriteZero,
+        ErrorKind::Interrupted => Host::Interrupted,
+        ErrorKind::UnexpectedEof => Host::UnexpectedEof,
+        _ => Host::Other,
+    }
+}
+
+#[cfg(feature = "std")]
+fn kind_from_host(kind: ::std::io::ErrorKind) -> ErrorKind {
+    use std::io::ErrorKind as Host;
+
+    match kind {
+        Host::NotFound => ErrorKind::NotFound,
+        Host::PermissionDenied => ErrorKind::PermissionDenied,
+        Host::ConnectionRefused => ErrorKind::ConnectionRefused,
+        Host::ConnectionReset => ErrorKind::ConnectionReset,
+        Host::ConnectionAborted => ErrorKind::ConnectionAborted,
+        Host::NotConnected => ErrorKind::NotConnected,
+        Host::AddrInUse => ErrorKind::AddrInUse,
+        Host::AddrNotAvailable => ErrorKind::AddrNotAvailable,
+        Host::BrokenPipe => ErrorKind::BrokenPipe,
+        Host::AlreadyExists => ErrorKind::AlreadyExists,
+        Host::WouldBlock => ErrorKind::WouldBlock,
+        Host::InvalidInput => ErrorKind::InvalidInput,
+        Host::InvalidData => ErrorKind::InvalidData,
+        Host::TimedOut => ErrorKind::TimedOut,
+        Host::WriteZero => ErrorKind::WriteZero,
+        Host::Interrupted => ErrorKind::Interrupted,
+        Host::UnexpectedEof => ErrorKind::UnexpectedEof,
+        _ => ErrorKind::Other,
+    }
+}
+
+// Carries a payload of ours inside a host error, along with its kind, which
+// the host may not be able to represent.
+#[cfg(feature = "std")]
+struct ToHost {
+    kind: ErrorKind,
+    error: Box<error::Error+Send+Sync>,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for ToHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.error, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ToHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ToHost {}
+
+// Carries a host payload inside one of our errors.
+#[cfg(feature = "std")]
+struct FromHost(Box<::std::error::Error+Send+Sync>);
+
+#[cfg(feature = "std")]
+impl fmt::Debug for FromHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for FromHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for FromHost {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+}
+
+/// Converts into the host's `std::io::Error`, keeping the OS error code or
+/// the custom payload.
+///
+/// OS error codes are passed through unchanged, so this is only meaningful
+/// when `STD` is a backend for the host platform. Kinds the host has no
+/// equivalent for become `std::io::ErrorKind::Other`; converting back
+/// restores them.
+#[cfg(feature = "std")]
+impl<STD: Std> From<Error<STD>> for ::std::io::Error where STD::OsError: Into<i32> {
+    fn from(err: Error<STD>) -> ::std::io::Error {
+        let our_kind = err.kind();
+        let kind = kind_to_host(our_kind);
+        match err.repr {
+            Repr::Os(code) => ::std::io::Error::from_raw_os_error(code.into()),
+            Repr::Simple(_) if kind_from_host(kind) == our_kind => ::std::io::Error::from(kind),
+            Repr::Custom(c) => match c.error.downcast::<FromHost>() {
+                Ok(host) => ::std::io::Error::new(kind, host.0),
+                Err(error) => ::std::io::Error::new(kind, ToHost { kind: our_kind, error: error }),
+            },
+            Repr::Simple(_) | Repr::SimpleMessage(_) | Repr::Context(_) => {
+                ::std::io::Error::new(kind, ToHost { kind: our_kind, error: Box::new(err) })
+            }
             Repr::__Std(..) => unreachable!(),
         }
     }
 }
 
+/// Converts from the host's `std::io::Error`, keeping the OS error code or
+/// the custom payload.
+///
+/// An error that was converted from an `io::Error` of the same platform is
+/// unwrapped again rather than nested, and the other conversion likewise
+/// unwraps host payloads.
+#[cfg(feature = "std")]
+impl<STD: Std> From<::std::io::Error> for Error<STD> where STD::OsError: From<i32> {
+    fn from(err: ::std::io::Error) -> Error<STD> {
+        if let Some(code) = err.raw_os_error() {
+            return Error::from_raw_os_error(STD::OsError::from(code));
+        }
+        let kind = kind_from_host(err.kind());
+        let payload = match err.into_inner() {
+            Some(payload) => payload,
+            None => return Error::from(kind),
+        };
+        match payload.downcast::<ToHost>() {
+            Ok(ours) => {
+                let ours = *ours;
+                match ours.error.downcast::<Error<STD>>() {
+                    Ok(err) => *err,
+                    Err(error) => Error::_new(ours.kind, error),
+                }
+            }
+            Err(payload) => Error::new(kind, FromHost(payload)),
+        }
+    }
+}
+
 fn _assert_error_is_sync_send<STD: Std>() {
     fn _is_sync_send<T: Sync+Send>() {}
     _is_sync_send::<Error<STD>>();
@@ -642,4 +1039,32 @@ mod test {
         let extracted = err.into_inner().unwrap();
         extracted.downcast::<TestError>().unwrap();
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_host_round_trip() {
+        use std::io::{Error as HostError, ErrorKind as HostErrorKind};
+
+        let err = Error::new(ErrorKind::Unsupported, "nope").with_context("ctx");
+        let host = HostError::from(err);
+        assert_eq!(host.kind(), HostErrorKind::Other);
+        let back = Error::from(host);
+        assert_eq!(back.kind(), ErrorKind::Unsupported);
+        assert_eq!(back.context(), Some("ctx"));
+        assert_eq!(back.to_string(), "ctx: nope");
+
+        let host = HostError::from(Error::from(ErrorKind::OutOfMemory));
+        assert_eq!(Error::from(host).kind(), ErrorKind::OutOfMemory);
+
+        let host = HostError::from(Error::new(ErrorKind::InvalidData, "bad"));
+        let back = Error::from(host);
+        assert_eq!(back.kind(), ErrorKind::InvalidData);
+        assert_eq!(back.to_string(), "bad");
+
+        let host = HostError::new(HostErrorKind::InvalidInput, "host");
+        let host = HostError::from(Error::from(host));
+        assert_eq!(host.kind(), HostErrorKind::InvalidInput);
+        assert!(host.get_ref().unwrap().downcast_ref::<super::ToHost>().is_none());
+        assert_eq!(host.to_string(), "host");
+    }
 }
@@ -281,7 +281,8 @@ use fmt;
 use result;
 use str;
 use memchr;
-use ptr;
+use mem;
+use ops::{Deref, DerefMut};
 
 // #[stable(feature = "rust1", since = "1.0.0")]
 // pub use self::buffered::{BufReader, BufWriter, LineWriter};
@@ -290,7 +291,14 @@ use ptr;
 // #[stable(feature = "rust1", since = "1.0.0")]
 // pub use self::cursor::Cursor;
 // #[stable(feature = "rust1", since = "1.0.0")]
+pub use self::async_io::{AsyncRead, AsyncWrite, AsyncSeek};
+pub use self::async_io::{AsyncReadExt, AsyncWriteExt, AsyncSeekExt};
+pub use self::async_io::{ReadFuture, ReadToEnd, WriteFuture, WriteAll, Flush, SeekFuture};
 pub use self::error::{Result, Error, ErrorKind};
+#[doc(hidden)]
+pub use self::error::SimpleMessage;
+pub use self::lazy::Lazy;
+pub use self::readbuf::{BorrowedBuf, BorrowedCursor, MaybeUninit};
 // #[stable(feature = "rust1", since = "1.0.0")]
 pub use self::util::{copy, sink, Sink, empty, Empty, repeat, Repeat};
 // #[stable(feature = "rust1", since = "1.0.0")]
@@ -303,12 +311,19 @@ pub use self::util::{copy, sink, Sink, empty, Empty, repeat, Repeat};
 // #[doc(no_inline, hidden)]
 // pub use self::stdio::{set_panic, set_print};
 
+// `error` goes first so the other modules can use `const_error!`.
+#[macro_use]
+mod error;
+#[cfg(feature = "std")]
+pub mod compat;
+pub mod poll;
 pub mod prelude;
+mod async_io;
 // mod buffered;
 // mod cursor;
-mod error;
 // mod impls;
-// mod lazy;
+mod lazy;
+mod readbuf;
 mod util;
 // mod stdio;
 
@@ -348,8 +363,8 @@ fn append_to_string<F, STD: Std>(buf: &mut String, f: F) -> Result<usize, STD>
         let ret = f(g.buf);
         if str::from_utf8(&g.buf[g.len..]).is_err() {
             ret.and_then(|_| {
-                Err(Error::new(ErrorKind::InvalidData,
-                               "stream did not contain valid UTF-8"))
+                Err(const_error!(ErrorKind::InvalidData,
+                                 "stream did not contain valid UTF-8"))
             })
         } else {
             g.len = g.buf.len();
@@ -365,37 +380,40 @@ fn append_to_string<F, STD: Std>(buf: &mut String, f: F) -> Result<usize, STD>
 // time is 4,500 times (!) slower than this if the reader has a very small
 // amount of data to return.
 //
-// Because we're extending the buffer with uninitialized data for trusted
-// readers, we need to make sure to truncate that if any of this panics.
+// The spare capacity of the vector is handed to the reader as a
+// `BorrowedBuf`, so it is never zeroed up front. Readers that initialize
+// more than they fill report so through the cursor, and that carries over
+// into the next iteration instead of being initialized again.
 fn read_to_end<R: Read<STD> + ?Sized, STD: Std>(r: &mut R, buf: &mut Vec<u8>) -> Result<usize, STD> {
     let start_len = buf.len();
-    let mut g = Guard { len: buf.len(), buf: buf };
-    let ret;
+    let mut initialized = 0;
     loop {
-        if g.len == g.buf.len() {
-            unsafe {
-                g.buf.reserve(32);
-                let capacity = g.buf.capacity();
-                g.buf.set_len(capacity);
-                r.initializer().initialize(&mut g.buf[g.len..]);
-            }
+        if buf.len() == buf.capacity() {
+            buf.reserve(32);
         }
 
-        match r.read(&mut g.buf[g.len..]) {
-            Ok(0) => {
-                ret = Ok(g.len - start_len);
-                break;
-            }
-            Ok(n) => g.len += n,
-            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
-            Err(e) => {
-                ret = Err(e);
-                break;
+        let (filled, init) = {
+            let mut read_buf: BorrowedBuf = readbuf::spare_capacity(buf).into();
+            unsafe { read_buf.set_init(initialized); }
+
+            match r.read_buf(read_buf.unfilled()) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
             }
+            (read_buf.len(), read_buf.init_len())
+        };
+
+        if filled == 0 {
+            return Ok(buf.len() - start_len);
         }
-    }
 
-    ret
+        initialized = init - filled;
+        unsafe {
+            let new_len = buf.len() + filled;
+            buf.set_len(new_len);
+        }
+    }
 }
 
 /// The `Read` trait allows for reading bytes from a source.
@@ -530,32 +548,31 @@ pub trait Read<STD: Std> {
     // #[stable(feature = "rust1", since = "1.0.0")]
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, STD>;
 
-    /// Determines if this `Read`er can work with buffers of uninitialized
-    /// memory.
+    /// Pull some bytes from this source into the specified buffer.
     ///
-    /// The default implementation returns an initializer which will zero
-    /// buffers.
+    /// This is equivalent to the [`read`](Read::read) method, except that it
+    /// is passed a [`BorrowedCursor`] rather than `[u8]` to allow use with
+    /// uninitialized buffers. The new data will be appended to any existing
+    /// contents of `cursor`.
     ///
-    /// If a `Read`er guarantees that it can work properly with uninitialized
-    /// memory, it should call [`Initializer::nop()`]. See the documentation for
-    /// [`Initializer`] for details.
+    /// The default implementation delegates to `read`.
     ///
-    /// The behavior of this method must be independent of the state of the
-    /// `Read`er - the method only takes `&self` so that it can be used through
-    /// trait objects.
-    ///
-    /// # Safety
+    /// This method makes it possible to return both data and an error but it
+    /// is advised against.
+    fn read_buf(&mut self, cursor: BorrowedCursor) -> Result<(), STD> {
+        default_read_buf(|b| self.read(b), cursor)
+    }
+
+    /// Like `read`, except that it reads into a slice of buffers.
     ///
-    /// This method is unsafe because a `Read`er could otherwise return a
-    /// non-zeroing `Initializer` from another `Read` type without an `unsafe`
-    /// block.
+    /// Data is copied to fill each buffer in order, with the final buffer
+    /// written to possibly being only partially filled. This method must
+    /// behave as a single call to `read` with the buffers concatenated would.
     ///
-    /// [`Initializer::nop()`]: ../../std/io/struct.Initializer.html#method.nop
-    /// [`Initializer`]: ../../std/io/struct.Initializer.html
-    #[unstable(feature = "read_initializer", issue = "42788")]
-    #[inline]
-    unsafe fn initializer(&self) -> Initializer {
-        Initializer::zeroing()
+    /// The default implementation calls `read` with the first nonempty
+    /// buffer provided, or an empty one if none exists.
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> Result<usize, STD> {
+        default_read_vectored(|b| self.read(b), bufs)
     }
 
     /// Read all bytes until EOF in this source, placing them into `buf`.
@@ -712,8 +729,8 @@ pub trait Read<STD: Std> {
             }
         }
         if !buf.is_empty() {
-            Err(Error::new(ErrorKind::UnexpectedEof,
-                           "failed to fill whole buffer"))
+            Err(const_error!(ErrorKind::UnexpectedEof,
+                             "failed to fill whole buffer"))
         } else {
             Ok(())
         }
@@ -911,50 +928,185 @@ pub trait Read<STD: Std> {
     }
 }
 
-/// A type used to conditionally initialize buffers passed to `Read` methods.
-#[unstable(feature = "read_initializer", issue = "42788")]
-#[derive(Debug)]
-pub struct Initializer(bool);
+/// A buffer type used with `Read::read_vectored`.
+///
+/// It is a wrapper around an `&mut [u8]`. Its layout is not that of an
+/// `iovec`, so backends using `readv`-style calls have to build their own
+/// array of buffers from it.
+pub struct IoSliceMut<'a>(&'a mut [u8]);
 
-impl Initializer {
-    /// Returns a new `Initializer` which will zero out buffers.
-    #[unstable(feature = "read_initializer", issue = "42788")]
+impl<'a> IoSliceMut<'a> {
+    /// Creates a new `IoSliceMut` wrapping a byte slice.
     #[inline]
-    pub fn zeroing() -> Initializer {
-        Initializer(true)
+    pub fn new(buf: &'a mut [u8]) -> IoSliceMut<'a> {
+        IoSliceMut(buf)
     }
 
-    /// Returns a new `Initializer` which will not zero out buffers.
+    /// Advances the internal cursor of the slice.
     ///
-    /// # Safety
+    /// # Panics
     ///
-    /// This may only be called by `Read`ers which guarantee that they will not
-    /// read from buffers passed to `Read` methods, and that the return value of
-    /// the method accurately reflects the number of bytes that have been
-    /// written to the head of the buffer.
-    #[unstable(feature = "read_initializer", issue = "42788")]
+    /// Panics if `n` is larger than the length of the slice.
     #[inline]
-    pub unsafe fn nop() -> Initializer {
-        Initializer(false)
+    pub fn advance(&mut self, n: usize) {
+        let buf = mem::replace(&mut self.0, &mut []);
+        self.0 = &mut buf[n..];
+    }
+
+    /// Advances a slice of slices by `n` bytes, removing the slices that were
+    /// consumed entirely and advancing the first remaining one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than the combined length of all slices.
+    pub fn advance_slices(bufs: &mut &mut [IoSliceMut<'a>], n: usize) {
+        let mut remove = 0;
+        let mut left = n;
+        for buf in bufs.iter() {
+            match left.checked_sub(buf.len()) {
+                Some(rest) => {
+                    left = rest;
+                    remove += 1;
+                }
+                None => break,
+            }
+        }
+
+        *bufs = &mut mem::replace(bufs, &mut [])[remove..];
+        if bufs.is_empty() {
+            assert!(left == 0, "advancing io slices beyond their length");
+        } else {
+            bufs[0].advance(left);
+        }
     }
+}
+
+impl<'a> Deref for IoSliceMut<'a> {
+    type Target = [u8];
 
-    /// Indicates if a buffer should be initialized.
-    #[unstable(feature = "read_initializer", issue = "42788")]
     #[inline]
-    pub fn should_initialize(&self) -> bool {
+    fn deref(&self) -> &[u8] {
         self.0
     }
+}
 
-    /// Initializes a buffer if necessary.
-    #[unstable(feature = "read_initializer", issue = "42788")]
+impl<'a> DerefMut for IoSliceMut<'a> {
     #[inline]
-    pub fn initialize(&self, buf: &mut [u8]) {
-        if self.should_initialize() {
-            unsafe { ptr::write_bytes(buf.as_mut_ptr(), 0, buf.len()) }
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.0
+    }
+}
+
+impl<'a> fmt::Debug for IoSliceMut<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+/// A buffer type used with `Write::write_vectored`.
+///
+/// It is a wrapper around an `&[u8]`. Its layout is not that of an `iovec`,
+/// so backends using `writev`-style calls have to build their own array of
+/// buffers from it.
+#[derive(Copy, Clone)]
+pub struct IoSlice<'a>(&'a [u8]);
+
+impl<'a> IoSlice<'a> {
+    /// Creates a new `IoSlice` wrapping a byte slice.
+    #[inline]
+    pub fn new(buf: &'a [u8]) -> IoSlice<'a> {
+        IoSlice(buf)
+    }
+
+    /// Advances the internal cursor of the slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than the length of the slice.
+    #[inline]
+    pub fn advance(&mut self, n: usize) {
+        self.0 = &self.0[n..];
+    }
+
+    /// Advances a slice of slices by `n` bytes, removing the slices that were
+    /// consumed entirely and advancing the first remaining one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than the combined length of all slices.
+    pub fn advance_slices(bufs: &mut &mut [IoSlice<'a>], n: usize) {
+        let mut remove = 0;
+        let mut left = n;
+        for buf in bufs.iter() {
+            match left.checked_sub(buf.len()) {
+                Some(rest) => {
+                    left = rest;
+                    remove += 1;
+                }
+                None => break,
+            }
         }
+
+        *bufs = &mut mem::replace(bufs, &mut [])[remove..];
+        if bufs.is_empty() {
+            assert!(left == 0, "advancing io slices beyond their length");
+        } else {
+            bufs[0].advance(left);
+        }
+    }
+}
+
+impl<'a> Deref for IoSlice<'a> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.0
     }
 }
 
+impl<'a> fmt::Debug for IoSlice<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.0, f)
+    }
+}
+
+/// Implements `read_vectored` in terms of a single-buffer `read`, by reading
+/// into the first nonempty buffer.
+pub fn default_read_vectored<F, STD: Std>(read: F, bufs: &mut [IoSliceMut]) -> Result<usize, STD>
+    where F: FnOnce(&mut [u8]) -> Result<usize, STD>
+{
+    let buf = bufs.iter_mut()
+        .find(|b| !b.is_empty())
+        .map_or(&mut [][..], |b| &mut **b);
+    read(buf)
+}
+
+/// Implements `read_buf` in terms of a single-buffer `read`, by initializing
+/// the unfilled part of the cursor and reading into it.
+pub fn default_read_buf<F, STD: Std>(read: F, mut cursor: BorrowedCursor) -> Result<(), STD>
+    where F: FnOnce(&mut [u8]) -> Result<usize, STD>
+{
+    let n = read(cursor.ensure_init().init_mut())?;
+    unsafe {
+        // SAFETY: we initialized using `ensure_init` so there is no
+        // uninit data to advance to.
+        cursor.advance(n);
+    }
+    Ok(())
+}
+
+/// Implements `write_vectored` in terms of a single-buffer `write`, by
+/// writing the first nonempty buffer.
+pub fn default_write_vectored<F, STD: Std>(write: F, bufs: &[IoSlice]) -> Result<usize, STD>
+    where F: FnOnce(&[u8]) -> Result<usize, STD>
+{
+    let buf = bufs.iter()
+        .find(|b| !b.is_empty())
+        .map_or(&[][..], |b| &**b);
+    write(buf)
+}
+
 /// A trait for objects which are byte-oriented sinks.
 ///
 /// Implementors of the `Write` trait are sometimes called 'writers'.
@@ -1041,6 +1193,18 @@ pub trait Write<STD: Std> {
     // #[stable(feature = "rust1", since = "1.0.0")]
     fn write(&mut self, buf: &[u8]) -> Result<usize, STD>;
 
+    /// Like `write`, except that it writes from a slice of buffers.
+    ///
+    /// Data is copied from each buffer in order, with the final buffer read
+    /// from possibly being only partially consumed. This method must behave
+    /// as a call to `write` with the buffers concatenated would.
+    ///
+    /// The default implementation calls `write` with the first nonempty
+    /// buffer provided, or an empty one if none exists.
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> Result<usize, STD> {
+        default_write_vectored(|b| self.write(b), bufs)
+    }
+
     /// Flush this output stream, ensuring that all intermediately buffered
     /// contents reach their destination.
     ///
@@ -1101,8 +1265,8 @@ pub trait Write<STD: Std> {
     fn write_all(&mut self, mut buf: &[u8]) -> Result<(), STD> {
         while !buf.is_empty() {
             match self.write(buf) {
-                Ok(0) => return Err(Error::new(ErrorKind::WriteZero,
-                                               "failed to write whole buffer")),
+                Ok(0) => return Err(const_error!(ErrorKind::WriteZero,
+                                                 "failed to write whole buffer")),
                 Ok(n) => buf = &buf[n..],
                 Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
                 Err(e) => return Err(e),
@@ -1111,6 +1275,34 @@ pub trait Write<STD: Std> {
         Ok(())
     }
 
+    /// Attempts to write multiple buffers into this writer.
+    ///
+    /// This method will continuously call [`write_vectored`] until there is
+    /// no more data to be written or an error of non-[`ErrorKind::Interrupted`]
+    /// kind is returned. This method will not return until all buffers have
+    /// been successfully written or such an error occurs.
+    ///
+    /// The slice of buffers is modified to track the progress of the write,
+    /// so its contents are unspecified once this method returns.
+    ///
+    /// [`write_vectored`]: #method.write_vectored
+    /// [`ErrorKind::Interrupted`]: ../../std/io/enum.ErrorKind.html#variant.Interrupted
+    fn write_all_vectored(&mut self, mut bufs: &mut [IoSlice]) -> Result<(), STD> {
+        // Guarantee that bufs is empty if it contains no data, to avoid
+        // calling write_vectored if there is no data to be written.
+        IoSlice::advance_slices(&mut bufs, 0);
+        while !bufs.is_empty() {
+            match self.write_vectored(bufs) {
+                Ok(0) => return Err(const_error!(ErrorKind::WriteZero,
+                                                 "failed to write whole buffer")),
+                Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
     /// Writes a formatted string into this writer, returning any error
     /// encountered.
     ///
@@ -1178,7 +1370,7 @@ pub trait Write<STD: Std> {
                 if output.error.is_err() {
                     output.error
                 } else {
-                    Err(Error::new(ErrorKind::Other, "formatter error"))
+                    Err(const_error!(ErrorKind::Other, "formatter error"))
                 }
             }
         }
@@ -1741,13 +1933,32 @@ impl<T: Read<STD>, U: Read<STD>, STD: Std> Read<STD> for Chain<T, U> {
         self.second.read(buf)
     }
 
-    unsafe fn initializer(&self) -> Initializer {
-        let initializer = self.first.initializer();
-        if initializer.should_initialize() {
-            initializer
-        } else {
-            self.second.initializer()
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> Result<usize, STD> {
+        if !self.done_first {
+            match self.first.read_vectored(bufs)? {
+                0 if bufs.iter().any(|b| !b.is_empty()) => self.done_first = true,
+                n => return Ok(n),
+            }
+        }
+        self.second.read_vectored(bufs)
+    }
+
+    fn read_buf(&mut self, mut cursor: BorrowedCursor) -> Result<(), STD> {
+        if cursor.capacity() == 0 {
+            return Ok(());
         }
+
+        if !self.done_first {
+            let old_len = cursor.written();
+            self.first.read_buf(cursor.reborrow())?;
+
+            if cursor.written() != old_len {
+                return Ok(());
+            } else {
+                self.done_first = true;
+            }
+        }
+        self.second.read_buf(cursor)
     }
 }
 
@@ -1939,8 +2150,65 @@ impl<T: Read<STD>, STD: Std> Read<STD> for Take<T> {
         Ok(n)
     }
 
-    unsafe fn initializer(&self) -> Initializer {
-        self.inner.initializer()
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> Result<usize, STD> {
+        if self.limit == 0 {
+            return Ok(0);
+        }
+
+        let total = bufs.iter().fold(0u64, |n, buf| n.saturating_add(buf.len() as u64));
+        if total <= self.limit {
+            let n = self.inner.read_vectored(bufs)?;
+            self.limit -= n as u64;
+            Ok(n)
+        } else {
+            // Cutting the buffers down to the limit would mean rebuilding
+            // the slice list, so read into a single buffer instead.
+            default_read_vectored(|b| self.read(b), bufs)
+        }
+    }
+
+    fn read_buf(&mut self, mut buf: BorrowedCursor) -> Result<(), STD> {
+        // Don't call into inner reader at all at EOF because it may still block
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        if self.limit <= buf.capacity() as u64 {
+            // if we just use an as cast to convert, limit may wrap around on a 32 bit target
+            let limit = cmp::min(self.limit, usize::max_value() as u64) as usize;
+
+            let extra_init = cmp::min(limit, buf.init_ref().len());
+
+            let (filled, new_init) = {
+                // SAFETY: no uninit data is written to ibuf
+                let ibuf = unsafe { &mut buf.as_mut()[..limit] };
+
+                let mut sliced_buf: BorrowedBuf = ibuf.into();
+
+                // SAFETY: extra_init bytes of ibuf are known to be initialized
+                unsafe {
+                    sliced_buf.set_init(extra_init);
+                }
+
+                self.inner.read_buf(sliced_buf.unfilled())?;
+                (sliced_buf.len(), sliced_buf.init_len())
+            };
+
+            // SAFETY: we initialized `new_init` bytes of the same memory
+            // that `buf` points at
+            unsafe {
+                buf.set_init(new_init);
+                buf.advance(filled);
+            }
+
+            self.limit -= filled as u64;
+        } else {
+            let written = buf.written();
+            self.inner.read_buf(buf.reborrow())?;
+            self.limit -= (buf.written() - written) as u64;
+        }
+
+        Ok(())
     }
 }
 
@@ -2162,7 +2430,7 @@ impl<B: BufRead<STD>, STD: Std> Iterator for Lines<B, STD> {
 mod tests {
     use io::prelude::*;
     use io;
-    use super::Cursor;
+    use super::{Cursor, IoSlice, BorrowedBuf};
     use test;
     use super::repeat;
 
@@ -2377,6 +2645,54 @@ mod tests {
         assert_eq!("AB", s);
     }
 
+    #[test]
+    fn io_slice_advance_slices() {
+        let buf1 = [1; 8];
+        let buf2 = [2; 16];
+        let buf3 = [3; 8];
+        let mut bufs = &mut [
+            IoSlice::new(&buf1),
+            IoSlice::new(&buf2),
+            IoSlice::new(&buf3),
+        ][..];
+
+        IoSlice::advance_slices(&mut bufs, 0);
+        assert_eq!(bufs.len(), 3);
+
+        IoSlice::advance_slices(&mut bufs, 8);
+        assert_eq!(&*bufs[0], &buf2);
+
+        IoSlice::advance_slices(&mut bufs, 10);
+        assert_eq!(&*bufs[0], &[2; 6]);
+        assert_eq!(&*bufs[1], &buf3);
+
+        IoSlice::advance_slices(&mut bufs, 14);
+        assert!(bufs.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn io_slice_advance_slices_beyond_total_length() {
+        let buf1 = [1; 8];
+        let mut bufs = &mut [IoSlice::new(&buf1)][..];
+
+        IoSlice::advance_slices(&mut bufs, 9);
+    }
+
+    #[test]
+    fn take_read_buf_respects_limit() {
+        let mut buf = [0; 8];
+        let mut rbuf: BorrowedBuf = (&mut buf[..]).into();
+
+        let mut r = repeat(7).take(5);
+        r.read_buf(rbuf.unfilled()).unwrap();
+        assert_eq!(rbuf.filled(), [7; 5]);
+        assert_eq!(rbuf.init_len(), 8);
+
+        r.read_buf(rbuf.unfilled()).unwrap();
+        assert_eq!(rbuf.len(), 5);
+    }
+
     #[bench]
     #[cfg_attr(target_os = "emscripten", ignore)]
     fn bench_read_to_end(b: &mut test::Bencher) {
@@ -0,0 +1,420 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Readiness polling for non-blocking I/O.
+//!
+//! A [`Poller`] watches a set of handles for read or write readiness and
+//! reports which of them became ready, identified by the [`Token`] they were
+//! registered with. Handles are meant to be put into non-blocking mode
+//! first, so that operations on a handle that isn't ready fail with
+//! `ErrorKind::WouldBlock` instead of stalling the event loop.
+//!
+//! Platforms with a kernel event queue (epoll, kqueue, ...) expose it
+//! through the `event_queue_*` hooks on `Std`. Everywhere else the poller
+//! falls back to `Std::poll`, which checks every registered handle on each
+//! call, the way `poll(2)` does. On platforms implementing neither,
+//! [`Poller::poll`] fails with `ErrorKind::Unsupported`.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use fs::File;
+//! use io::poll::{Poller, Events, Interest, Token};
+//! use time::Duration;
+//!
+//! # fn foo() -> io::Result<(), MyPlatform> {
+//! let pipe = File::<MyPlatform>::open("/tmp/fifo")?;
+//! pipe.set_nonblocking(true)?;
+//!
+//! let mut poller = Poller::<MyPlatform>::new()?;
+//! poller.register(&pipe, Token(0), Interest::READABLE)?;
+//!
+//! let mut events = Events::with_capacity(16);
+//! poller.poll(&mut events, Some(Duration::from_millis(100)))?;
+//! for event in events.iter() {
+//!     assert_eq!(event.token(), Token(0));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Poller::poll`]: struct.Poller.html#method.poll
+
+use prelude::*;
+use traits::{self, Std};
+
+use fmt;
+use fs;
+use io::{self, ErrorKind};
+use iter;
+use ops::BitOr;
+use sys_common::AsInner;
+use time::Duration;
+
+/// The kind of readiness a handle is registered for.
+///
+/// The same set is used to report which kind of readiness an [`Event`]
+/// carries.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Interest(u8);
+
+const READABLE: u8 = 0b01;
+const WRITABLE: u8 = 0b10;
+
+impl Interest {
+    /// Interest in read readiness.
+    pub const READABLE: Interest = Interest(READABLE);
+
+    /// Interest in write readiness.
+    pub const WRITABLE: Interest = Interest(WRITABLE);
+
+    /// Returns an empty set, as reported for handles that aren't ready.
+    pub const fn empty() -> Interest {
+        Interest(0)
+    }
+
+    /// Returns the union of `self` and `other`.
+    pub const fn add(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+
+    /// Returns `true` if the set contains no interest at all.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if the set contains read readiness.
+    pub fn is_readable(&self) -> bool {
+        self.0 & READABLE != 0
+    }
+
+    /// Returns `true` if the set contains write readiness.
+    pub fn is_writable(&self) -> bool {
+        self.0 & WRITABLE != 0
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        self.add(other)
+    }
+}
+
+impl fmt::Debug for Interest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.is_readable(), self.is_writable()) {
+            (true, true) => f.write_str("READABLE | WRITABLE"),
+            (true, false) => f.write_str("READABLE"),
+            (false, true) => f.write_str("WRITABLE"),
+            (false, false) => f.write_str("(empty)"),
+        }
+    }
+}
+
+/// Identifies a registered handle in the events returned by
+/// [`Poller::poll`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Token(pub usize);
+
+/// A readiness event for a registered handle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    token: Token,
+    ready: Interest,
+    error: bool,
+}
+
+impl Event {
+    /// Creates an event reporting `ready` for the handle registered as
+    /// `token`, with `error` set if the handle is in an error or hang-up
+    /// state.
+    pub const fn new(token: Token, ready: Interest, error: bool) -> Event {
+        Event { token: token, ready: ready, error: error }
+    }
+
+    /// The token the handle was registered with.
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    /// Returns `true` if the handle is ready for reading.
+    pub fn is_readable(&self) -> bool {
+        self.ready.is_readable()
+    }
+
+    /// Returns `true` if the handle is ready for writing.
+    pub fn is_writable(&self) -> bool {
+        self.ready.is_writable()
+    }
+
+    /// Returns `true` if the handle is in an error or hang-up state.
+    ///
+    /// The next operation on the handle reports the actual error.
+    pub fn is_error(&self) -> bool {
+        self.error
+    }
+}
+
+/// A buffer of events filled in by [`Poller::poll`].
+pub struct Events {
+    buf: Vec<Event>,
+    len: usize,
+}
+
+impl Events {
+    /// Creates a buffer that holds up to `capacity` events per call to
+    /// `poll`.
+    pub fn with_capacity(capacity: usize) -> Events {
+        Events {
+            buf: iter::repeat(Event::new(Token(0), Interest::empty(), false))
+                .take(capacity)
+                .collect(),
+            len: 0,
+        }
+    }
+
+    /// The maximum number of events returned per call to `poll`.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns `true` if the last call to `poll` returned no events.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the events returned by the last call to
+    /// `poll`.
+    pub fn iter(&self) -> ::slice::Iter<Event> {
+        self.buf[..self.len].iter()
+    }
+
+    /// Removes all events.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a Events {
+    type Item = &'a Event;
+    type IntoIter = ::slice::Iter<'a, Event>;
+
+    fn into_iter(self) -> ::slice::Iter<'a, Event> {
+        self.iter()
+    }
+}
+
+/// A handle that can be registered with a [`Poller`].
+///
+/// Sockets and pipes are surfaced as `STD::File` handles on the platforms
+/// that have them, so this is implemented by everything that wraps one.
+pub trait Source<STD: Std> {
+    /// The platform handle to watch.
+    fn as_handle(&self) -> &STD::File;
+}
+
+impl<STD: Std> Source<STD> for fs::File<STD> {
+    fn as_handle(&self) -> &STD::File {
+        self.as_inner()
+    }
+}
+
+/// An entry handed to `Std::poll`.
+///
+/// The platform sets `ready` to the subset of `interest` the handle is ready
+/// for, and `error` if it is in an error or hang-up state.
+pub struct PollFd<'a, STD: Std + 'a> {
+    pub handle: &'a STD::File,
+    pub interest: Interest,
+    pub ready: Interest,
+    pub error: bool,
+}
+
+struct Registration<STD: Std> {
+    token: Token,
+    interest: Interest,
+    // Owned duplicate of the registered handle, so registrations stay valid
+    // however long the caller keeps the original around.
+    handle: STD::File,
+}
+
+/// Waits for readiness events on a set of registered handles.
+pub struct Poller<STD: Std> {
+    queue: Option<usize>,
+    registrations: Vec<Registration<STD>>,
+}
+
+impl<STD: Std> Poller<STD> {
+    /// Creates a new poller, backed by the platform's event queue if it has
+    /// one.
+    pub fn new() -> io::Result<Poller<STD>, STD> {
+        Ok(Poller {
+            queue: STD::event_queue_new()?,
+            registrations: Vec::new(),
+        })
+    }
+
+    /// Starts watching `source` for `interest`, reporting its events with
+    /// `token`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `interest` is empty, and with
+    /// `ErrorKind::AlreadyExists` if `token` is already registered.
+    pub fn register<S>(&mut self, source: &S, token: Token, interest: Interest)
+                       -> io::Result<(), STD>
+        where S: Source<STD> + ?Sized
+    {
+        if interest.is_empty() {
+            return Err(const_error!(ErrorKind::InvalidInput,
+                                    "cannot register with an empty interest"));
+        }
+        if self.position(token).is_some() {
+            return Err(const_error!(ErrorKind::AlreadyExists,
+                                    "token is already registered"));
+        }
+
+        let handle = traits::File::duplicate(source.as_handle())?;
+        if let Some(queue) = self.queue {
+            STD::event_queue_register(queue, &handle, token, interest)?;
+        }
+        self.registrations.push(Registration {
+            token: token,
+            interest: interest,
+            handle: handle,
+        });
+        Ok(())
+    }
+
+    /// Changes the interest of the handle registered as `token`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `interest` is empty, and with
+    /// `ErrorKind::NotFound` if `token` isn't registered.
+    pub fn reregister(&mut self, token: Token, interest: Interest) -> io::Result<(), STD> {
+        if interest.is_empty() {
+            return Err(const_error!(ErrorKind::InvalidInput,
+                                    "cannot register with an empty interest"));
+        }
+        let i = self.find(token)?;
+
+        if let Some(queue) = self.queue {
+            STD::event_queue_modify(queue, &self.registrations[i].handle, token, interest)?;
+        }
+        self.registrations[i].interest = interest;
+        Ok(())
+    }
+
+    /// Stops watching the handle registered as `token`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::NotFound` if `token` isn't registered.
+    pub fn deregister(&mut self, token: Token) -> io::Result<(), STD> {
+        let i = self.find(token)?;
+
+        if let Some(queue) = self.queue {
+            STD::event_queue_deregister(queue, &self.registrations[i].handle)?;
+        }
+        self.registrations.swap_remove(i);
+        Ok(())
+    }
+
+    /// Waits until at least one registered handle is ready, or `timeout`
+    /// elapses, and stores the resulting events in `events`.
+    ///
+    /// A `timeout` of `None` waits indefinitely, and a zero timeout returns
+    /// immediately. Readiness is level-triggered: a handle keeps being
+    /// reported for as long as it is ready.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `events` has no capacity, or
+    /// if nothing is registered and `timeout` is `None`, since such a wait
+    /// could never return. Errors from the platform are returned as is,
+    /// including `ErrorKind::Interrupted` if the wait was interrupted by a
+    /// signal.
+    pub fn poll(&mut self, events: &mut Events, timeout: Option<Duration>)
+                -> io::Result<(), STD> {
+        events.clear();
+
+        if events.buf.is_empty() {
+            return Err(const_error!(ErrorKind::InvalidInput,
+                                    "events has no capacity"));
+        }
+        if self.registrations.is_empty() && timeout.is_none() {
+            return Err(const_error!(ErrorKind::InvalidInput,
+                                    "nothing is registered to wait for"));
+        }
+
+        if let Some(queue) = self.queue {
+            events.len = STD::event_queue_wait(queue, &mut events.buf, timeout)?;
+            return Ok(());
+        }
+
+        let mut fds: Vec<PollFd<STD>> = self.registrations.iter().map(|reg| {
+            PollFd {
+                handle: &reg.handle,
+                interest: reg.interest,
+                ready: Interest::empty(),
+                error: false,
+            }
+        }).collect();
+        STD::poll(&mut fds, timeout)?;
+
+        for (fd, reg) in fds.iter().zip(&self.registrations) {
+            if events.len == events.buf.len() {
+                break;
+            }
+            if fd.ready.is_empty() && !fd.error {
+                continue;
+            }
+            events.buf[events.len] = Event::new(reg.token, fd.ready, fd.error);
+            events.len += 1;
+        }
+        Ok(())
+    }
+
+    fn position(&self, token: Token) -> Option<usize> {
+        self.registrations.iter().position(|reg| reg.token == token)
+    }
+
+    fn find(&self, token: Token) -> io::Result<usize, STD> {
+        self.position(token).ok_or_else(|| {
+            const_error!(ErrorKind::NotFound, "token is not registered")
+        })
+    }
+}
+
+impl<STD: Std> Drop for Poller<STD> {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue {
+            STD::event_queue_close(queue);
+        }
+    }
+}
+
+impl<STD: Std> fmt::Debug for Poller<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Poller")
+            .field("queue", &self.queue)
+            .field("registrations", &self.registrations.len())
+            .finish()
+    }
+}
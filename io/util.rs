@@ -15,8 +15,9 @@ use traits::Std;
 use marker::PhantomData;
 
 use core::fmt;
-use io::{self, Read, Initializer, Write, ErrorKind, BufRead};
-use core::mem;
+use io::{self, Read, Write, ErrorKind, BufRead, IoSlice, BorrowedBuf, BorrowedCursor, MaybeUninit};
+use fs::File;
+use sys_common::AsInner;
 
 /// Copies the entire contents of a reader into a writer.
 ///
@@ -49,26 +50,130 @@ use core::mem;
 /// # }
 /// # foo().unwrap();
 /// ```
+///
+/// # Platform-specific behavior
+///
+/// If both `reader` and `writer` are [`fs::File`]s (owned, `&File` or
+/// `&mut File`), the copy is offloaded to the platform through
+/// [`File::copy_file_range`] where supported, so no data passes through
+/// userspace. Files behind any other wrapper are copied through a buffer.
+/// If `reader` implements [`BufRead`], its internal buffer is written out
+/// directly instead of being copied into an intermediate buffer first.
+///
+/// [`fs::File`]: ../fs/struct.File.html
+/// [`File::copy_file_range`]: ../traits/trait.File.html#method.copy_file_range
+/// [`BufRead`]: trait.BufRead.html
 // #[stable(feature = "rust1", since = "1.0.0")]
 pub fn copy<R: ?Sized, W: ?Sized, STD: Std>(reader: &mut R, writer: &mut W) -> io::Result<u64, STD>
     where R: Read<STD>, W: Write<STD>
 {
-    let mut buf = unsafe {
-        let mut buf: [u8; super::DEFAULT_BUF_SIZE] = mem::uninitialized();
-        reader.initializer().initialize(&mut buf);
-        buf
-    };
+    let mut written = 0;
+    if let (Some(src), Some(dst)) = (reader.as_file(), writer.as_file()) {
+        let (n, done) = offload_copy(src, dst)?;
+        if done {
+            return Ok(n);
+        }
+        written = n;
+    }
+    Ok(written + reader.buffered_copy(writer)?)
+}
+
+// The number of bytes requested from the platform per offloaded copy call.
+const OFFLOAD_CHUNK: u64 = 1 << 30;
 
+// Copies between two files through the platform's copy offload. Returns the
+// number of bytes copied and whether the copy reached EOF; if the platform
+// declines, the remainder has to be copied by the caller.
+fn offload_copy<STD: Std>(src: &File<STD>, dst: &File<STD>) -> io::Result<(u64, bool), STD> {
     let mut written = 0;
     loop {
-        let len = match reader.read(&mut buf) {
-            Ok(0) => return Ok(written),
-            Ok(len) => len,
-            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+        match src.as_inner().copy_file_range(dst.as_inner(), OFFLOAD_CHUNK) {
+            Ok(Some(0)) => return Ok((written, true)),
+            Ok(Some(n)) => written += n,
+            Ok(None) => return Ok((written, false)),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
             Err(e) => return Err(e),
-        };
-        writer.write_all(&buf[..len])?;
-        written += len as u64;
+        }
+    }
+}
+
+// Detects readers and writers that are files, to offload the copy.
+trait AsFile<STD: Std> {
+    fn as_file(&self) -> Option<&File<STD>>;
+}
+
+impl<T: ?Sized, STD: Std> AsFile<STD> for T {
+    default fn as_file(&self) -> Option<&File<STD>> {
+        None
+    }
+}
+
+impl<STD: Std> AsFile<STD> for File<STD> {
+    fn as_file(&self) -> Option<&File<STD>> {
+        Some(self)
+    }
+}
+
+impl<'a, STD: Std> AsFile<STD> for &'a File<STD> {
+    fn as_file(&self) -> Option<&File<STD>> {
+        Some(*self)
+    }
+}
+
+impl<'a, STD: Std> AsFile<STD> for &'a mut File<STD> {
+    fn as_file(&self) -> Option<&File<STD>> {
+        Some(&**self)
+    }
+}
+
+// Copies through an intermediate buffer, or straight out of the reader's
+// own buffer if it has one.
+trait BufferedCopy<STD: Std> {
+    fn buffered_copy<W: Write<STD> + ?Sized>(&mut self, writer: &mut W) -> io::Result<u64, STD>;
+}
+
+impl<R: Read<STD> + ?Sized, STD: Std> BufferedCopy<STD> for R {
+    default fn buffered_copy<W: Write<STD> + ?Sized>(&mut self, writer: &mut W)
+                                                    -> io::Result<u64, STD> {
+        let mut buf = [MaybeUninit::<u8>::uninit(); super::DEFAULT_BUF_SIZE];
+        let mut buf: BorrowedBuf = (&mut buf[..]).into();
+
+        let mut written = 0;
+        loop {
+            buf.clear();
+            match self.read_buf(buf.unfilled()) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            if buf.len() == 0 {
+                return Ok(written);
+            }
+            writer.write_all(buf.filled())?;
+            written += buf.len() as u64;
+        }
+    }
+}
+
+impl<R: BufRead<STD> + ?Sized, STD: Std> BufferedCopy<STD> for R {
+    fn buffered_copy<W: Write<STD> + ?Sized>(&mut self, writer: &mut W) -> io::Result<u64, STD> {
+        let mut written = 0;
+        loop {
+            let len = {
+                let buf = match self.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                if buf.is_empty() {
+                    return Ok(written);
+                }
+                writer.write_all(buf)?;
+                buf.len()
+            };
+            self.consume(len);
+            written += len as u64;
+        }
     }
 }
 
@@ -107,9 +212,7 @@ impl<STD: Std> Read<STD> for Empty<STD> {
     fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize, STD> { Ok(0) }
 
     #[inline]
-    unsafe fn initializer(&self) -> Initializer {
-        Initializer::nop()
-    }
+    fn read_buf(&mut self, _cursor: BorrowedCursor) -> io::Result<(), STD> { Ok(()) }
 }
 // #[stable(feature = "rust1", since = "1.0.0")]
 impl<STD: Std> BufRead<STD> for Empty<STD> {
@@ -162,9 +265,20 @@ impl<STD: Std> Read<STD> for Repeat<STD> {
         Ok(buf.len())
     }
 
-    #[inline]
-    unsafe fn initializer(&self) -> Initializer {
-        Initializer::nop()
+    fn read_buf(&mut self, mut cursor: BorrowedCursor) -> io::Result<(), STD> {
+        // SAFETY: No uninit bytes are being written
+        for slot in unsafe { cursor.as_mut() } {
+            *slot = MaybeUninit::new(self.byte);
+        }
+
+        let remaining = cursor.capacity();
+
+        // SAFETY: the entire unfilled portion of buf has been initialized
+        unsafe {
+            cursor.advance(remaining);
+        }
+
+        Ok(())
     }
 }
 
@@ -206,6 +320,10 @@ impl<STD: Std> Write<STD> for Sink<STD> {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize, STD> { Ok(buf.len()) }
     #[inline]
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize, STD> {
+        Ok(bufs.iter().map(|b| b.len()).sum())
+    }
+    #[inline]
     fn flush(&mut self) -> io::Result<(), STD> { Ok(()) }
 }
 
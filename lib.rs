@@ -16,9 +16,17 @@
 #![feature(toowned_clone_into)]
 #![feature(str_internals)]
 #![feature(rustc_attrs)]
+#![feature(asm)]
+#![feature(fnbox)]
+#![feature(specialization)]
+#![feature(associated_type_defaults)]
 
 extern crate alloc;
 extern crate std_unicode;
+#[cfg(feature = "std")]
+extern crate std;
+#[cfg(feature = "log")]
+extern crate log;
 
 pub use core::any;
 pub use core::cell;
@@ -59,6 +67,7 @@ pub use std_unicode::char;
 // pub use core::u128;
 
 pub mod error;
+#[macro_use]
 pub mod io;
 pub mod os;
 pub mod traits;
@@ -70,6 +79,15 @@ pub mod memchr;
 pub mod fs;
 pub mod time;
 pub mod util;
+pub mod heap;
+pub mod panic;
+pub mod backtrace;
+pub mod rt;
+pub mod process;
+pub mod env;
+pub mod task;
+#[cfg(feature = "log")]
+pub mod logger;
 
 // Copied 1:1 from std (except for use prelude::*)
 pub mod ascii;
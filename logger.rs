@@ -0,0 +1,139 @@
+// Copyright 2017 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A [`log`] sink writing to the platform's standard error.
+//!
+//! Only available with the `log` feature.
+//!
+//! ```ignore
+//! StderrLogger::<MyPlatform>::from_env("RUST_LOG").init().unwrap();
+//! ```
+//!
+//! [`log`]: https://docs.rs/log
+
+use prelude::*;
+use traits::Std;
+
+use cell::UnsafeCell;
+use env;
+use fmt;
+use io::Write;
+use log::{self, Log, LevelFilter, Metadata, Record, SetLoggerError};
+use sys_common::mutex::Mutex;
+use util;
+
+/// A logger printing records as `LEVEL target: message`.
+///
+/// Records are written to [`Std::Stderr`] unless a different writer is set
+/// with [`with_writer`]. Records above the configured level are discarded.
+///
+/// [`Std::Stderr`]: ../traits/trait.Std.html#associatedtype.Stderr
+/// [`with_writer`]: #method.with_writer
+pub struct StderrLogger<STD: Std> {
+    level: LevelFilter,
+    lock: Mutex<STD>,
+    writer: Option<UnsafeCell<Box<Write<STD> + Send>>>,
+}
+
+// The writer is only accessed with `lock` held.
+unsafe impl<STD: Std> Sync for StderrLogger<STD> {}
+unsafe impl<STD: Std> Send for StderrLogger<STD> {}
+
+impl<STD: Std> StderrLogger<STD> {
+    /// Creates a logger printing errors only.
+    pub fn new() -> StderrLogger<STD> {
+        StderrLogger {
+            level: LevelFilter::Error,
+            lock: Mutex::new(),
+            writer: None,
+        }
+    }
+
+    /// Creates a logger whose level is read from the environment variable
+    /// `key`, e.g. `RUST_LOG=debug`.
+    ///
+    /// Falls back to errors only if the variable is unset or not a valid
+    /// level.
+    pub fn from_env(key: &str) -> StderrLogger<STD> {
+        let level = env::var::<_, STD>(key).ok()
+            .and_then(|level| level.trim().parse().ok())
+            .unwrap_or(LevelFilter::Error);
+        StderrLogger::new().with_level(level)
+    }
+
+    /// Sets the maximum level of records to print.
+    pub fn with_level(mut self, level: LevelFilter) -> StderrLogger<STD> {
+        self.level = level;
+        self
+    }
+
+    /// Writes records to `writer` instead of the platform's standard error.
+    pub fn with_writer<W: Write<STD> + Send + 'static>(mut self, writer: W) -> StderrLogger<STD> {
+        self.writer = Some(UnsafeCell::new(Box::new(writer)));
+        self
+    }
+
+    /// Returns the maximum level of records printed.
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    /// Installs this logger as the global logger of the `log` crate.
+    ///
+    /// The logger is leaked, as the `log` crate requires a `'static` logger.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        let logger: &'static StderrLogger<STD> = unsafe { &*Box::into_raw(Box::new(self)) };
+        log::set_logger(logger)?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<STD: Std> Log for StderrLogger<STD> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let args = format_args!("{:<5} {}: {}\n", record.level(), record.target(), record.args());
+        match self.writer {
+            Some(ref writer) => unsafe {
+                self.lock.lock();
+                let _ = (*writer.get()).write_fmt(args);
+                self.lock.unlock();
+            },
+            None => util::dumb_print::<STD>(args),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref writer) = self.writer {
+            unsafe {
+                self.lock.lock();
+                let _ = (*writer.get()).flush();
+                self.lock.unlock();
+            }
+        }
+    }
+}
+
+impl<STD: Std> fmt::Debug for StderrLogger<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StderrLogger")
+            .field("level", &self.level)
+            .field("redirected", &self.writer.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Panic support.
+//!
+//! This module implements the runtime side of panicking: printing the panic
+//! message, running the user-settable panic hook and aborting the process.
+//! There is no unwinding support, so every panic ends in
+//! [`Std::abort_internal`].
+//!
+//! A binary hooks this up with the [`panic_handler!`] macro, which defines
+//! the `panic_fmt` lang item and so needs `#![feature(lang_items)]`:
+//!
+//! ```ignore
+//! #![feature(lang_items)]
+//!
+//! panic_handler!(MyPlatform);
+//! ```
+//!
+//! [`Std::abort_internal`]: ../traits/trait.Std.html#tymethod.abort_internal
+//! [`panic_handler!`]: ../macro.panic_handler.html
+
+use prelude::*;
+use traits::Std;
+
+use any::Any;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use fmt;
+use mem;
+use sys_common::spin::SpinLock;
+use util;
+
+/// Information about a panic, passed to the panic hook.
+#[derive(Debug)]
+pub struct PanicInfo<'a> {
+    payload: &'a (Any + Send),
+    message: Option<&'a fmt::Arguments<'a>>,
+    location: Location<'a>,
+}
+
+impl<'a> PanicInfo<'a> {
+    /// The payload of the panic.
+    ///
+    /// Panics raised through `panic!` carry their message as a `&str` or
+    /// `String` payload, if they carry one at all.
+    pub fn payload(&self) -> &(Any + Send) {
+        self.payload
+    }
+
+    /// The formatted message of the panic, if any.
+    pub fn message(&self) -> Option<&fmt::Arguments> {
+        self.message
+    }
+
+    /// Where the panic was raised.
+    ///
+    /// This always returns `Some`; the `Option` leaves room for panics
+    /// without a known location.
+    pub fn location(&self) -> Option<&Location> {
+        Some(&self.location)
+    }
+}
+
+/// The location a panic was raised at.
+#[derive(Debug, Clone, Copy)]
+pub struct Location<'a> {
+    file: &'a str,
+    line: u32,
+    col: u32,
+}
+
+impl<'a> Location<'a> {
+    /// The source file the panic was raised in.
+    pub fn file(&self) -> &str {
+        self.file
+    }
+
+    /// The line the panic was raised on.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The column the panic was raised at.
+    pub fn column(&self) -> u32 {
+        self.col
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Hook {
+    Default,
+    Custom(*mut (Fn(&PanicInfo) + Sync + Send + 'static)),
+}
+
+static HOOK_LOCK: SpinLock = SpinLock::new();
+static mut HOOK: Hook = Hook::Default;
+
+// Without thread-local storage the panic state is kept process-wide: the
+// thread currently handling a panic (as `Std::current_thread_id() + 1`, so
+// that 0 means none) and how deep its nested panics go. A panic on another
+// thread waits for the hook lock instead of counting as nested.
+static PANIC_THREAD: AtomicUsize = AtomicUsize::new(0);
+static PANIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers a custom panic hook, replacing any that was previously
+/// registered.
+///
+/// The panic hook is invoked when a thread panics, before the process is
+/// aborted. The default hook prints a message to standard error; the hook
+/// passed here replaces it.
+///
+/// # Panics
+///
+/// Panics if called while a panic is in progress.
+pub fn set_hook(hook: Box<Fn(&PanicInfo) + Sync + Send + 'static>) {
+    if panicking() {
+        panic!("cannot modify the panic hook from a panicking thread");
+    }
+
+    unsafe {
+        HOOK_LOCK.lock();
+        let old_hook = HOOK;
+        HOOK = Hook::Custom(Box::into_raw(hook));
+        HOOK_LOCK.unlock();
+
+        if let Hook::Custom(ptr) = old_hook {
+            Box::from_raw(ptr);
+        }
+    }
+}
+
+/// Unregisters the current panic hook, returning it.
+///
+/// If no custom hook is registered, the default hook for `STD` is returned.
+///
+/// # Panics
+///
+/// Panics if called while a panic is in progress.
+pub fn take_hook<STD: Std>() -> Box<Fn(&PanicInfo) + Sync + Send + 'static> {
+    if panicking() {
+        panic!("cannot modify the panic hook from a panicking thread");
+    }
+
+    unsafe {
+        HOOK_LOCK.lock();
+        let hook = mem::replace(&mut HOOK, Hook::Default);
+        HOOK_LOCK.unlock();
+
+        match hook {
+            Hook::Default => Box::new(default_hook::<STD>),
+            Hook::Custom(ptr) => Box::from_raw(ptr),
+        }
+    }
+}
+
+/// Returns whether a panic is currently in progress.
+///
+/// As there is no thread-local storage, this is true while any thread is
+/// panicking, not just the current one.
+pub fn panicking() -> bool {
+    PANIC_COUNT.load(Ordering::SeqCst) != 0
+}
+
+/// The default panic hook.
+///
+/// Prints `thread '<name>' panicked at '<msg>', file:line:col` to the
+/// platform's standard error.
+pub fn default_hook<STD: Std>(info: &PanicInfo) {
+    let msg = Message(info);
+    STD::with_thread_name(&mut |name| {
+        let name = name.unwrap_or("<unnamed>");
+        match info.location() {
+            Some(location) => util::dumb_print::<STD>(format_args!(
+                "thread '{}' panicked at '{}', {}:{}:{}\n",
+                name, msg, location.file(), location.line(), location.column())),
+            None => util::dumb_print::<STD>(format_args!(
+                "thread '{}' panicked at '{}'\n", name, msg)),
+        }
+    });
+}
+
+// Displays the message of a panic, falling back to a string payload.
+struct Message<'a, 'b: 'a>(&'a PanicInfo<'b>);
+
+impl<'a, 'b> fmt::Display for Message<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(args) = self.0.message() {
+            return fmt::write(f, *args);
+        }
+        let payload: &(Any + Send) = self.0.payload();
+        match payload.downcast_ref::<&'static str>() {
+            Some(s) => f.write_str(s),
+            None => match payload.downcast_ref::<String>() {
+                Some(s) => f.write_str(s),
+                None => f.write_str("Box<Any>"),
+            },
+        }
+    }
+}
+
+/// Entry point of panics, to be called from the `panic_fmt` lang item.
+///
+/// Runs the panic hook and aborts the process. A panic raised on the same
+/// thread while the hook is running, or while a previous panic is still
+/// being processed, aborts immediately through [`util::abort`].
+///
+/// Threads are told apart by `Std::current_thread_id`. On platforms that
+/// don't implement it, a panic raised while another one is being handled
+/// can't be attributed to either thread: it is reported with the default
+/// hook, without waiting for the other panic, and the process aborts.
+///
+/// [`util::abort`]: ../util/fn.abort.html
+pub fn begin_panic_handler<STD: Std>(msg: fmt::Arguments, file: &'static str,
+                                     line: u32, col: u32) -> ! {
+    struct NoPayload;
+
+    let info = PanicInfo {
+        payload: &NoPayload,
+        message: Some(&msg),
+        location: Location { file: file, line: line, col: col },
+    };
+    let info = &info;
+
+    let id = STD::current_thread_id();
+    let thread = id.wrapping_add(1);
+    let panics = if id == 0 {
+        // Without thread ids a panic raised while another one is being
+        // handled may come from the same thread or not. Waiting for the hook
+        // lock could deadlock, so such a panic is reported on its own.
+        if unsafe { HOOK_LOCK.try_lock() } {
+            PANIC_COUNT.store(1, Ordering::SeqCst);
+            1
+        } else {
+            if PANIC_COUNT.fetch_add(1, Ordering::SeqCst) + 1 > 2 {
+                util::abort::<STD>(format_args!("thread panicked while processing panic. aborting."));
+            }
+            default_hook::<STD>(info);
+            unsafe { STD::abort_internal() }
+        }
+    } else if PANIC_THREAD.load(Ordering::SeqCst) == thread {
+        PANIC_COUNT.fetch_add(1, Ordering::SeqCst) + 1
+    } else {
+        // Another thread may be running the hook; it's going to abort the
+        // process once done, but let it finish first.
+        unsafe { HOOK_LOCK.lock(); }
+        PANIC_THREAD.store(thread, Ordering::SeqCst);
+        PANIC_COUNT.store(1, Ordering::SeqCst);
+        1
+    };
+
+    // If this is the third nested call, the panic hook itself panicked
+    // while handling a double panic.
+    if panics > 2 {
+        util::abort::<STD>(format_args!("thread panicked while processing panic. aborting."));
+    }
+
+    // A nested panic may have been raised by the custom hook itself, so
+    // only the default hook is run in that case. This also avoids
+    // reacquiring the hook lock.
+    if panics > 1 {
+        default_hook::<STD>(info);
+        util::abort::<STD>(format_args!("thread panicked while panicking. aborting."));
+    }
+
+    // The hook lock is held from here until the process aborts.
+    unsafe {
+        match HOOK {
+            Hook::Default => default_hook::<STD>(info),
+            Hook::Custom(ptr) => (*ptr)(info),
+        }
+        STD::abort_internal()
+    }
+}
+
+/// Defines the `panic_fmt` lang item of a binary in terms of
+/// [`panic::begin_panic_handler`] for the given platform.
+///
+/// The crate using it needs `#![feature(lang_items)]`.
+///
+/// [`panic::begin_panic_handler`]: panic/fn.begin_panic_handler.html
+#[macro_export]
+macro_rules! panic_handler {
+    ($std:ty) => {
+        #[lang = "panic_fmt"]
+        #[no_mangle]
+        pub extern fn rust_begin_panic(msg: ::core::fmt::Arguments, file: &'static str,
+                                       line: u32, col: u32) -> ! {
+            $crate::panic::begin_panic_handler::<$std>(msg, file, line, col)
+        }
+    }
+}
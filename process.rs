@@ -0,0 +1,48 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Working with the current process.
+
+// #![stable(feature = "process", since = "1.0.0")]
+
+use traits::Std;
+
+use rt;
+
+/// Terminates the current process with the specified exit code.
+///
+/// Before the platform's [`Std::exit`] is called, all handlers registered
+/// with [`rt::at_exit`] are run and buffered standard output is flushed
+/// through [`Std::flush_stdout`]. Running the handlers also releases every
+/// [`io::Lazy`] global; the value in one is only dropped, flushing any
+/// buffered writer, if no other `Arc` to it is still alive.
+///
+/// Destructors on the current stack or any other thread's stack will not be
+/// run.
+///
+/// [`Std::exit`]: ../traits/trait.Std.html#method.exit
+/// [`Std::flush_stdout`]: ../traits/trait.Std.html#method.flush_stdout
+/// [`rt::at_exit`]: ../rt/fn.at_exit.html
+/// [`io::Lazy`]: ../io/struct.Lazy.html
+// #[stable(feature = "rust1", since = "1.0.0")]
+pub fn exit<STD: Std>(code: i32) -> ! {
+    rt::cleanup();
+    // There's nowhere left to report a failure to.
+    let _ = STD::flush_stdout();
+    STD::exit(code)
+}
+
+/// Terminates the process in an abnormal fashion.
+///
+/// No `at_exit` handlers are run and nothing is flushed.
+// #[stable(feature = "process_abort", since = "1.17.0")]
+pub fn abort<STD: Std>() -> ! {
+    unsafe { STD::abort_internal() }
+}
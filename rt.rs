@@ -0,0 +1,30 @@
+// Copyright 2013 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Runtime services
+//!
+//! The `rt` module provides a narrow set of runtime services, currently the
+//! registration and running of the handlers executed on an orderly exit.
+
+// Reexport some of our utilities which are expected by other crates.
+pub use sys_common::at_exit;
+
+use sys_common::at_exit_imp;
+
+/// Runs all registered `at_exit` handlers.
+///
+/// Handlers registered while this runs are executed as well, up to a fixed
+/// number of rounds. Afterwards no more handlers can be registered. This is
+/// called by `process::exit` and should only be called directly by code
+/// that terminates the process by other means. Only the first call does
+/// anything.
+pub fn cleanup() {
+    at_exit_imp::cleanup();
+}
@@ -0,0 +1,90 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Implementation of running at_exit routines
+//!
+//! Documentation can be found on the `rt::at_exit` function.
+
+use prelude::*;
+
+use boxed::FnBox;
+use core::sync::atomic::{AtomicBool, Ordering};
+use ptr;
+use sys_common::spin::SpinLock;
+
+type Queue = Vec<Box<FnBox()>>;
+
+// NB the queue isn't tied to a platform, so it is protected by a spin lock
+// rather than a platform `Mutex`. Registration and cleanup are rare enough
+// that spinning doesn't matter.
+static LOCK: SpinLock = SpinLock::new();
+static mut QUEUE: *mut Queue = ptr::null_mut();
+
+// The maximum number of times the cleanup routines will be run. While running
+// the at_exit closures new ones may be registered, and this count is the number
+// of times the new closures will be allowed to register successfully. After
+// this number of iterations all new registrations will return `false`.
+const ITERS: usize = 10;
+
+static CLEANED_UP: AtomicBool = AtomicBool::new(false);
+
+unsafe fn init() -> bool {
+    if QUEUE.is_null() {
+        let state: Box<Queue> = Box::new(Vec::new());
+        QUEUE = Box::into_raw(state);
+    } else if QUEUE as usize == 1 {
+        // can't re-init after a cleanup
+        return false
+    }
+
+    true
+}
+
+pub fn cleanup() {
+    // Only the first call runs the handlers; later ones, say from a second
+    // `process::exit` racing the first, are no-ops.
+    if CLEANED_UP.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    for i in 0..ITERS {
+        unsafe {
+            LOCK.lock();
+            let queue = QUEUE;
+            QUEUE = if i == ITERS - 1 {1} else {0} as *mut _;
+            LOCK.unlock();
+
+            // make sure we're not recursively cleaning up
+            assert!(queue as usize != 1);
+
+            // If we never called init, not need to cleanup!
+            if !queue.is_null() {
+                let queue: Box<Queue> = Box::from_raw(queue);
+                for to_run in *queue {
+                    to_run();
+                }
+            }
+        }
+    }
+}
+
+pub fn push(f: Box<FnBox()>) -> bool {
+    let mut ret = true;
+    unsafe {
+        LOCK.lock();
+        if init() {
+            (*QUEUE).push(f);
+        } else {
+            ret = false;
+        }
+        LOCK.unlock();
+    }
+    ret
+}
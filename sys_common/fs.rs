@@ -0,0 +1,378 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Generic implementations of filesystem operations on `Std`.
+//!
+//! These only use the basic path operations (`readdir`, `lstat`, `unlink`,
+//! `rmdir`), file handles and, where the backend has them, the optional
+//! handle-relative hooks, so every backend gets them for free. Backends with
+//! a native implementation should override the `Std` methods instead.
+
+use prelude::*;
+use traits::{Std, File, FileAttr, FileType, DirEntry, OpenOptions};
+
+use io::{self, ErrorKind};
+use path::Path;
+use sys_common::mutex::Mutex;
+
+/// How often a directory is emptied again when new entries keep appearing
+/// while it's being removed.
+const REMOVE_ATTEMPTS: usize = 8;
+
+/// Removes `path` and everything below it.
+///
+/// A symlink at `path` is removed itself rather than the directory it
+/// points to, and entries that disappear while the tree is being removed
+/// are skipped rather than reported.
+///
+/// With `Std::open_dir_at` every directory is opened without following
+/// symlinks and its entries are looked up and removed relative to that
+/// handle, so a symlink anywhere in the tree is unlinked and never
+/// followed, even if it replaces a directory mid-removal. Entries are still
+/// listed through the path, so a directory replaced that way is left in
+/// place and the removal fails.
+///
+/// Without those hooks this goes by path and is race-prone: a directory is
+/// checked with `lstat` before it is read, and swapping it for a symlink in
+/// between makes the removal follow the symlink. Where `FileAttr::file_id`
+/// is available the directory is checked again after reading it, which
+/// catches most but not all such swaps and fails with `NotADirectory`.
+pub fn remove_dir_all<STD: Std>(path: &Path<STD>) -> io::Result<(), STD> {
+    let attr = STD::lstat(path)?;
+    if attr.file_type().is_symlink() {
+        return STD::unlink(path);
+    }
+    match STD::open_dir_at(None, path)? {
+        Some(dir) => remove_dir_all_at(None, dir, path, path),
+        None => remove_dir_all_recursive(path, attr.file_id()),
+    }
+}
+
+// Empties and removes the directory open as `dir`, which is `name` inside
+// `parent` or the path `name` if there's no parent. `path` is only used to
+// list its entries.
+fn remove_dir_all_at<STD: Std>(parent: Option<&STD::File>, dir: STD::File, path: &Path<STD>,
+                               name: &Path<STD>) -> io::Result<(), STD> {
+    let mut attempts = 0;
+    loop {
+        let entries = match STD::readdir(path) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let child_name = entry.file_name();
+            let child_name = Path::new(&child_name);
+
+            let attr = match STD::lstat_at(&dir, child_name) {
+                Ok(attr) => attr,
+                Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let ret = if attr.file_type().is_dir() {
+                match STD::open_dir_at(Some(&dir), child_name) {
+                    Ok(Some(child)) => {
+                        remove_dir_all_at(Some(&dir), child, &entry.path(), child_name)
+                    }
+                    Ok(None) => Err(const_error!(ErrorKind::Unsupported,
+                                                 "handle-relative operations are not supported")),
+                    Err(e) => Err(e),
+                }
+            } else {
+                STD::unlink_at(&dir, child_name, false)
+            };
+            match ret {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let ret = match parent {
+            Some(parent) => STD::unlink_at(parent, name, true),
+            None => STD::rmdir(path),
+        };
+        match ret {
+            Ok(()) => return Ok(()),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(ref e) if e.kind() == ErrorKind::DirectoryNotEmpty
+                          && attempts < REMOVE_ATTEMPTS => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn remove_dir_all_recursive<STD: Std>(path: &Path<STD>, id: Option<(u64, u64)>)
+                                      -> io::Result<(), STD> {
+    let mut attempts = 0;
+    loop {
+        let entries = match STD::readdir(path) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        check_same_dir(path, id)?;
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let child = entry.path();
+
+            // Go by `lstat` rather than the entry's cached type, so what we
+            // descend into is what is there now.
+            let attr = match STD::lstat(&child) {
+                Ok(attr) => attr,
+                Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let ret = if attr.file_type().is_dir() {
+                remove_dir_all_recursive(&child, attr.file_id())
+            } else {
+                STD::unlink(&child)
+            };
+            match ret {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        match STD::rmdir(path) {
+            Ok(()) => return Ok(()),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(ref e) if e.kind() == ErrorKind::DirectoryNotEmpty
+                          && attempts < REMOVE_ATTEMPTS => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn check_same_dir<STD: Std>(path: &Path<STD>, id: Option<(u64, u64)>) -> io::Result<(), STD> {
+    let id = match id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let attr = STD::lstat(path)?;
+    if attr.file_type().is_dir() && attr.file_id() == Some(id) {
+        Ok(())
+    } else {
+        Err(const_error!(ErrorKind::NotADirectory, "directory was replaced during removal"))
+    }
+}
+
+/// Copies the contents and permissions of the regular file `from` to `to`,
+/// returning the number of bytes copied.
+///
+/// `to` is created or truncated. The copy is offloaded through
+/// `File::copy_file_range` when the platform supports it.
+pub fn copy<STD: Std>(from: &Path<STD>, to: &Path<STD>) -> io::Result<u64, STD> {
+    let mut opts = STD::OpenOptions::new();
+    opts.read(true);
+    let reader = STD::File::open(from, &opts)?;
+    let attr = reader.file_attr()?;
+    if !attr.file_type().is_file() {
+        return Err(const_error!(ErrorKind::InvalidInput,
+                                "the source path is not an existing regular file"));
+    }
+
+    let mut opts = STD::OpenOptions::new();
+    opts.write(true);
+    opts.create(true);
+    opts.truncate(true);
+    let writer = STD::File::open(to, &opts)?;
+    writer.set_permissions(attr.perm())?;
+
+    let mut written = 0;
+    while written < attr.size() {
+        match reader.copy_file_range(&writer, attr.size() - written) {
+            Ok(Some(0)) | Ok(None) => break,
+            Ok(Some(n)) => written += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Whatever wasn't offloaded, including anything the file grew by.
+    let mut buf = [0; super::io::DEFAULT_BUF_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(written),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let mut buf = &buf[..n];
+        while !buf.is_empty() {
+            match writer.write(buf) {
+                Ok(0) => return Err(const_error!(ErrorKind::WriteZero,
+                                                 "failed to write whole buffer")),
+                Ok(m) => buf = &buf[m..],
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        written += n as u64;
+    }
+}
+
+/// Serializes the seek-based `read_at`/`write_at` fallbacks on one file, so
+/// that two positional calls never see each other's cursor.
+///
+/// Plain reads, writes and seeks don't take it and can still observe the
+/// temporarily moved cursor. Backends with `NATIVE_POSITIONAL_IO` get no
+/// mutex at all.
+pub struct PositionalLock<STD: Std> {
+    // Boxed so it doesn't move along with the file.
+    mutex: Option<Box<Mutex<STD>>>,
+}
+
+// The mutex is only ever used through `&self`, like the `Sync` it has.
+unsafe impl<STD: Std> Send for PositionalLock<STD> {}
+
+impl<STD: Std> PositionalLock<STD> {
+    /// Creates the lock for a newly opened file.
+    pub fn new() -> PositionalLock<STD> {
+        let mutex = if <STD::File as File<STD>>::NATIVE_POSITIONAL_IO {
+            None
+        } else {
+            let mut mutex = Box::new(Mutex::new());
+            unsafe { mutex.init(); }
+            Some(mutex)
+        };
+        PositionalLock { mutex: mutex }
+    }
+
+    /// Blocks until no other positional call on the file is running.
+    pub fn lock(&self) -> PositionalGuard<STD> {
+        let mutex = self.mutex.as_ref().map(|mutex| &**mutex);
+        if let Some(mutex) = mutex {
+            unsafe { mutex.lock(); }
+        }
+        PositionalGuard(mutex)
+    }
+}
+
+impl<STD: Std> Drop for PositionalLock<STD> {
+    fn drop(&mut self) {
+        if let Some(ref mutex) = self.mutex {
+            unsafe { mutex.destroy(); }
+        }
+    }
+}
+
+pub struct PositionalGuard<'a, STD: Std + 'a>(Option<&'a Mutex<STD>>);
+
+impl<'a, STD: Std> Drop for PositionalGuard<'a, STD> {
+    fn drop(&mut self) {
+        if let Some(mutex) = self.0 {
+            unsafe { mutex.unlock(); }
+        }
+    }
+}
+
+/// Emulates `pread` by seeking to `offset`, reading and seeking back.
+///
+/// Callers must keep other users of the cursor out, see `PositionalLock`.
+/// What was read is returned even if seeking back fails.
+pub fn read_at<STD: Std, F: File<STD>>(file: &F, buf: &mut [u8], offset: u64)
+                                       -> io::Result<usize, STD> {
+    let pos = file.seek(io::SeekFrom::Current(0))?;
+    file.seek(io::SeekFrom::Start(offset))?;
+    let ret = file.read(buf);
+    // The read already happened, so its result matters more than a cursor
+    // left at the wrong place.
+    let _ = file.seek(io::SeekFrom::Start(pos));
+    ret
+}
+
+/// Emulates `pwrite` by seeking to `offset`, writing and seeking back.
+///
+/// Callers must keep other users of the cursor out, see `PositionalLock`.
+/// What was written is returned even if seeking back fails, so callers
+/// don't write it twice.
+pub fn write_at<STD: Std, F: File<STD>>(file: &F, buf: &[u8], offset: u64)
+                                        -> io::Result<usize, STD> {
+    let pos = file.seek(io::SeekFrom::Current(0))?;
+    file.seek(io::SeekFrom::Start(offset))?;
+    let ret = file.write(buf);
+    let _ = file.seek(io::SeekFrom::Start(pos));
+    ret
+}
+
+#[cfg(all(test, not(any(target_os = "cloudabi", target_os = "emscripten"))))]
+mod tests {
+    use prelude::*;
+
+    use fs;
+    use sys_common::io::test::tmpdir;
+    use thread;
+
+    #[test]
+    #[cfg(unix)]
+    fn remove_dir_all_unlinks_inner_symlinks() {
+        use os::unix::fs::symlink;
+
+        let tmpdir = tmpdir();
+        let root = tmpdir.join("root");
+        let outside = tmpdir.join("outside");
+        let canary = outside.join("do_not_delete");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(&canary, b"foo").unwrap();
+        symlink(&outside, root.join("dir_link")).unwrap();
+        symlink(&canary, root.join("sub").join("file_link")).unwrap();
+
+        super::remove_dir_all(&root).unwrap();
+        assert!(!root.exists());
+        assert!(outside.is_dir());
+        assert_eq!(fs::read(&canary).unwrap(), b"foo");
+
+        // A symlink at the top is removed itself.
+        let link = tmpdir.join("link");
+        symlink(&outside, &link).unwrap();
+        super::remove_dir_all(&link).unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(canary.exists());
+    }
+
+    #[test]
+    fn remove_dir_all_skips_vanished_entries() {
+        const FILES: usize = 500;
+
+        let tmpdir = tmpdir();
+        let root = tmpdir.join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        let mut files = Vec::new();
+        for i in 0..FILES {
+            let dir = if i % 2 == 0 { root.clone() } else { root.join("sub") };
+            let file = dir.join(format!("file{}", i));
+            fs::write(&file, b"").unwrap();
+            files.push(file);
+        }
+
+        let remover = thread::spawn(move || {
+            for file in files.iter().rev() {
+                let _ = fs::remove_file(file);
+            }
+        });
+        super::remove_dir_all(&root).unwrap();
+        remover.join().unwrap();
+        assert!(!root.exists());
+    }
+}
@@ -13,38 +13,22 @@ pub const DEFAULT_BUF_SIZE: usize = 8 * 1024;
 #[allow(dead_code)] // not used on emscripten
 pub mod test {
     use path::{Path, PathBuf};
-    use env;
-    use rand::{self, Rng};
     use fs;
+    use traits::Std;
 
-    pub struct TempDir(PathBuf);
+    pub struct TempDir<STD: Std>(fs::TempDir<STD>);
 
-    impl TempDir {
-        pub fn join(&self, path: &str) -> PathBuf {
-            let TempDir(ref p) = *self;
-            p.join(path)
+    impl<STD: Std> TempDir<STD> {
+        pub fn join(&self, path: &str) -> PathBuf<STD> {
+            self.0.path().join(path)
         }
 
-        pub fn path<'a>(&'a self) -> &'a Path {
-            let TempDir(ref p) = *self;
-            p
+        pub fn path<'a>(&'a self) -> &'a Path<STD> {
+            self.0.path()
         }
     }
 
-    impl Drop for TempDir {
-        fn drop(&mut self) {
-            // Gee, seeing how we're testing the fs module I sure hope that we
-            // at least implement this correctly!
-            let TempDir(ref p) = *self;
-            fs::remove_dir_all(p).unwrap();
-        }
-    }
-
-    pub fn tmpdir() -> TempDir {
-        let p = env::temp_dir();
-        let mut r = rand::thread_rng();
-        let ret = p.join(&format!("rust-{}", r.next_u32()));
-        fs::create_dir(&ret).unwrap();
-        TempDir(ret)
+    pub fn tmpdir<STD: Std>() -> TempDir<STD> {
+        TempDir(fs::TempDir::new().unwrap())
     }
 }
@@ -22,9 +22,15 @@
 //! between them all would form a dag, facilitating the extraction of
 //! `std::sys` from the standard library.
 
+use prelude::*;
+
+pub mod at_exit_imp;
 pub mod bytestring;
+pub mod fs;
 pub mod io;
 pub mod mutex;
+pub mod spin;
+pub mod thread_pool;
 
 /// A trait for viewing representations from std types
 #[doc(hidden)]
@@ -48,4 +54,18 @@ pub trait IntoInner<Inner> {
 #[doc(hidden)]
 pub trait FromInner<Inner> {
     fn from_inner(inner: Inner) -> Self;
-}
\ No newline at end of file
+}
+
+/// Enqueues a procedure to run when the process exits.
+///
+/// These closures are run by `process::exit`. Once the `at_exit` handlers
+/// begin running, more may be enqueued, but not infinitely so. Eventually a
+/// handler registration will be forced to fail.
+///
+/// Returns `Ok` if the handler was successfully registered, meaning that the
+/// closure will be run once the process exits. Returns `Err` to indicate
+/// that the closure could not be registered, meaning that it is not scheduled
+/// to be run.
+pub fn at_exit<F: FnOnce() + Send + 'static>(f: F) -> Result<(), ()> {
+    if at_exit_imp::push(Box::new(f)) {Ok(())} else {Err(())}
+}
@@ -0,0 +1,47 @@
+// Copyright 2017 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A platform-independent spin lock.
+///
+/// Process-wide state that is not generic over a platform (and therefore
+/// can't use the platform [`Mutex`]) is protected by one of these. It is only
+/// suitable for short, rarely contended critical sections.
+///
+/// [`Mutex`]: ../mutex/struct.Mutex.html
+pub struct SpinLock(AtomicBool);
+
+impl SpinLock {
+    /// Creates a new, unlocked spin lock.
+    pub const fn new() -> SpinLock { SpinLock(AtomicBool::new(false)) }
+
+    /// Locks the spin lock, busy-waiting until it is available.
+    #[inline]
+    pub unsafe fn lock(&self) {
+        while !self.try_lock() {}
+    }
+
+    /// Attempts to lock the spin lock without waiting, returning whether it
+    /// was successfully acquired or not.
+    #[inline]
+    pub unsafe fn try_lock(&self) -> bool {
+        !self.0.compare_and_swap(false, true, Ordering::Acquire)
+    }
+
+    /// Unlocks the spin lock.
+    ///
+    /// Behavior is undefined if the current thread does not actually hold the
+    /// lock.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        self.0.store(false, Ordering::Release)
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A small pool of threads for blocking work done on behalf of async code.
+//!
+//! Jobs are handed to `spawn_blocking`, which returns a future resolving to
+//! the job's result. Workers are started lazily through `Std::spawn_thread`
+//! and park when there is nothing to do. A worker that keeps finding the
+//! queue empty exits, so on platforms where `Std::park_thread` returns right
+//! away idle workers don't spin forever; they are started again for the
+//! next job. On platforms without threads the job runs inline and the
+//! returned future is ready right away.
+
+use prelude::*;
+use traits::Std;
+
+use alloc::arc::Arc;
+use alloc::vec_deque::VecDeque;
+use boxed::FnBox;
+use cell::UnsafeCell;
+use fmt;
+use ptr;
+use sys_common::spin::SpinLock;
+use task::{Context, Future, Poll, Waker};
+
+/// The most threads the pool will start.
+const MAX_WORKERS: usize = 4;
+
+/// How many times in a row an idle worker wakes up to an empty queue before
+/// it exits.
+const MAX_IDLE_WAKEUPS: usize = 64;
+
+type Job = Box<FnBox() + Send>;
+
+/// An idle worker: its thread id and how to unpark it. Workers of different
+/// platforms share the pool, so the id only means something to the
+/// `unpark_thread` of the platform that started it.
+type Idle = (usize, fn(usize));
+
+struct Pool {
+    jobs: VecDeque<Job>,
+    idle: Vec<Idle>,
+    workers: usize,
+}
+
+// NB the pool isn't tied to a platform, so like the at_exit queue it is
+// protected by a spin lock. It only guards pushing and popping jobs.
+static LOCK: SpinLock = SpinLock::new();
+static mut POOL: *mut Pool = ptr::null_mut();
+
+unsafe fn pool() -> &'static mut Pool {
+    if POOL.is_null() {
+        POOL = Box::into_raw(Box::new(Pool {
+            jobs: VecDeque::new(),
+            idle: Vec::new(),
+            workers: 0,
+        }));
+    }
+    &mut *POOL
+}
+
+fn worker<STD: Std>() {
+    let me: Idle = (STD::current_thread_id(), STD::unpark_thread);
+    let mut idle_wakeups = 0;
+    loop {
+        let job = unsafe {
+            LOCK.lock();
+            let pool = pool();
+            let job = if let Some(job) = pool.jobs.pop_front() {
+                Some(job)
+            } else if idle_wakeups == MAX_IDLE_WAKEUPS {
+                // Retire. Any job submitted from now on sees one worker less
+                // and starts a new one.
+                pool.idle.retain(|&idle| idle != me);
+                pool.workers -= 1;
+                LOCK.unlock();
+                return;
+            } else {
+                if !pool.idle.contains(&me) {
+                    pool.idle.push(me);
+                }
+                None
+            };
+            LOCK.unlock();
+            job
+        };
+
+        match job {
+            Some(job) => {
+                idle_wakeups = 0;
+                job()
+            }
+            None => {
+                STD::park_thread();
+                idle_wakeups += 1;
+            }
+        }
+    }
+}
+
+fn submit<STD: Std>(job: Job) {
+    let (wake, spawn) = unsafe {
+        LOCK.lock();
+        let pool = pool();
+        pool.jobs.push_back(job);
+        let wake = pool.idle.pop();
+        let spawn = wake.is_none() && pool.workers < MAX_WORKERS;
+        if spawn {
+            pool.workers += 1;
+        }
+        LOCK.unlock();
+        (wake, spawn)
+    };
+
+    if let Some((id, unpark)) = wake {
+        unpark(id);
+    }
+    if !spawn || STD::spawn_thread(Box::new(worker::<STD>)).is_ok() {
+        return;
+    }
+
+    // Starting a worker failed. If there are none at all nobody is going to
+    // pick up the queue, so run it here.
+    let jobs = unsafe {
+        LOCK.lock();
+        let pool = pool();
+        pool.workers -= 1;
+        let jobs = if pool.workers == 0 {
+            pool.jobs.drain(..).collect()
+        } else {
+            Vec::new()
+        };
+        LOCK.unlock();
+        jobs
+    };
+    for job in jobs {
+        job();
+    }
+}
+
+struct Shared<T> {
+    lock: SpinLock,
+    value: UnsafeCell<Option<T>>,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// A future resolving to the result of a job passed to `spawn_blocking`.
+pub struct Blocking<T> {
+    shared: Arc<Shared<T>>,
+    done: bool,
+}
+
+/// Runs `f` on the blocking thread pool.
+pub fn spawn_blocking<STD, F, T>(f: F) -> Blocking<T>
+    where STD: Std,
+          F: FnOnce() -> T + Send + 'static,
+          T: Send + 'static
+{
+    let shared = Arc::new(Shared {
+        lock: SpinLock::new(),
+        value: UnsafeCell::new(None),
+        waker: UnsafeCell::new(None),
+    });
+
+    let job_shared = shared.clone();
+    submit::<STD>(Box::new(move || {
+        let value = f();
+        let waker = unsafe {
+            job_shared.lock.lock();
+            *job_shared.value.get() = Some(value);
+            let waker = (*job_shared.waker.get()).take();
+            job_shared.lock.unlock();
+            waker
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }));
+
+    Blocking { shared: shared, done: false }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<T> {
+        assert!(!self.done, "`Blocking` polled after completion");
+        let value = unsafe {
+            self.shared.lock.lock();
+            let value = (*self.shared.value.get()).take();
+            if value.is_none() {
+                *self.shared.waker.get() = Some(cx.waker().clone());
+            }
+            self.shared.lock.unlock();
+            value
+        };
+        match value {
+            Some(value) => {
+                self.done = true;
+                Poll::Ready(value)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Blocking<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Blocking").field("done", &self.done).finish()
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright 2018 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Futures and running them to completion.
+//!
+//! [`Future`] is a poll-based computation in the style of the `futures`
+//! crate: it is polled through `&mut self`, so a future must stay valid
+//! when moved between polls, and there is no `async`/`.await` sugar. A
+//! future that can't make progress returns [`Poll::Pending`] after cloning
+//! the [`Waker`] from its [`Context`], and wakes it once it can.
+//!
+//! [`block_on`] drives a single future on the current thread, and
+//! [`Executor`] drives a set of spawned tasks. Both park the thread through
+//! `Std::park_thread` while nothing can make progress and are woken by the
+//! tasks' wakers through `Std::unpark_thread`. Blocking work can be moved
+//! off the thread with [`spawn_blocking`].
+//!
+//! [`Future`]: trait.Future.html
+//! [`Poll::Pending`]: enum.Poll.html#variant.Pending
+//! [`Waker`]: struct.Waker.html
+//! [`Context`]: struct.Context.html
+//! [`block_on`]: fn.block_on.html
+//! [`Executor`]: struct.Executor.html
+//! [`spawn_blocking`]: fn.spawn_blocking.html
+
+use prelude::*;
+use traits::Std;
+
+use alloc::arc::Arc;
+use cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use fmt;
+use marker::PhantomData;
+use mem;
+use sys_common::spin::SpinLock;
+
+pub use sys_common::thread_pool::{spawn_blocking, Blocking};
+
+/// The result of polling a [`Future`].
+///
+/// [`Future`]: trait.Future.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Poll<T> {
+    /// The value is ready.
+    Ready(T),
+    /// The value is not ready yet; the task will be woken once it may be.
+    Pending,
+}
+
+impl<T> Poll<T> {
+    /// Maps the value of a ready poll with `f`.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Poll<U> {
+        match self {
+            Poll::Ready(t) => Poll::Ready(f(t)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Returns `true` if this is `Poll::Ready`.
+    pub fn is_ready(&self) -> bool {
+        match *self {
+            Poll::Ready(_) => true,
+            Poll::Pending => false,
+        }
+    }
+
+    /// Returns `true` if this is `Poll::Pending`.
+    pub fn is_pending(&self) -> bool {
+        !self.is_ready()
+    }
+}
+
+/// An asynchronous computation.
+///
+/// `poll` is called until it returns `Poll::Ready`. When it returns
+/// `Poll::Pending` it must have arranged for the waker of `cx` to be woken
+/// once polling again may make progress. Polling a future again after it
+/// completed may panic.
+pub trait Future {
+    /// The value the future resolves to.
+    type Output;
+
+    /// Attempts to resolve the future to its value.
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output>;
+}
+
+impl<'a, F: Future + ?Sized> Future for &'a mut F {
+    type Output = F::Output;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<F::Output> {
+        (**self).poll(cx)
+    }
+}
+
+impl<F: Future + ?Sized> Future for Box<F> {
+    type Output = F::Output;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<F::Output> {
+        (**self).poll(cx)
+    }
+}
+
+/// Something that can be woken, to be wrapped in a [`Waker`].
+///
+/// [`Waker`]: struct.Waker.html
+pub trait Wake: Send + Sync {
+    /// Wakes the task this waker belongs to.
+    fn wake(&self);
+}
+
+/// A handle for waking up a task.
+#[derive(Clone)]
+pub struct Waker {
+    inner: Arc<Wake>,
+}
+
+impl Waker {
+    /// Creates a waker calling `wake` on `w`.
+    pub fn new<W: Wake + 'static>(w: Arc<W>) -> Waker {
+        Waker { inner: w }
+    }
+
+    /// Wakes up the task, consuming the waker.
+    pub fn wake(self) {
+        self.inner.wake()
+    }
+
+    /// Wakes up the task without consuming the waker.
+    pub fn wake_by_ref(&self) {
+        self.inner.wake()
+    }
+}
+
+impl fmt::Debug for Waker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Waker")
+    }
+}
+
+/// The context a future is polled in.
+#[derive(Debug)]
+pub struct Context<'a> {
+    waker: &'a Waker,
+}
+
+impl<'a> Context<'a> {
+    /// Creates a context from a waker.
+    pub fn from_waker(waker: &'a Waker) -> Context<'a> {
+        Context { waker: waker }
+    }
+
+    /// The waker of the task being polled.
+    pub fn waker(&self) -> &'a Waker {
+        self.waker
+    }
+}
+
+struct Parker<STD: Std> {
+    thread: usize,
+    notified: AtomicBool,
+    _std: PhantomData<STD>,
+}
+
+impl<STD: Std> Wake for Parker<STD> {
+    fn wake(&self) {
+        self.notified.store(true, Ordering::Release);
+        STD::unpark_thread(self.thread);
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking it while the
+/// future is pending.
+///
+/// On platforms without thread parking this spins until the future is
+/// woken.
+pub fn block_on<F: Future, STD: Std>(mut future: F) -> F::Output {
+    let parker = Arc::new(Parker::<STD> {
+        thread: STD::current_thread_id(),
+        notified: AtomicBool::new(false),
+        _std: PhantomData,
+    });
+    let waker = Waker::new(parker.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(ret) = future.poll(&mut cx) {
+            return ret;
+        }
+        while !parker.notified.swap(false, Ordering::Acquire) {
+            STD::park_thread();
+        }
+    }
+}
+
+struct ReadyQueue<STD: Std> {
+    thread: usize,
+    lock: SpinLock,
+    ready: UnsafeCell<Vec<usize>>,
+    _std: PhantomData<STD>,
+}
+
+unsafe impl<STD: Std> Send for ReadyQueue<STD> {}
+unsafe impl<STD: Std> Sync for ReadyQueue<STD> {}
+
+impl<STD: Std> ReadyQueue<STD> {
+    fn push(&self, task: usize) {
+        unsafe {
+            self.lock.lock();
+            (*self.ready.get()).push(task);
+            self.lock.unlock();
+        }
+    }
+
+    fn take(&self) -> Vec<usize> {
+        unsafe {
+            self.lock.lock();
+            let ready = mem::replace(&mut *self.ready.get(), Vec::new());
+            self.lock.unlock();
+            ready
+        }
+    }
+}
+
+struct TaskWaker<STD: Std> {
+    task: usize,
+    queue: Arc<ReadyQueue<STD>>,
+}
+
+impl<STD: Std> Wake for TaskWaker<STD> {
+    fn wake(&self) {
+        self.queue.push(self.task);
+        STD::unpark_thread(self.queue.thread);
+    }
+}
+
+/// A single-threaded executor.
+///
+/// Tasks are polled on the thread that created the executor, whenever
+/// their waker is woken, until they complete.
+///
+/// # Examples
+///
+/// ```ignore
+/// use task::{Context, Executor, Future, Poll};
+///
+/// struct Hello;
+///
+/// impl Future for Hello {
+///     type Output = ();
+///
+///     fn poll(&mut self, _cx: &mut Context) -> Poll<()> {
+///         println!("Hello, world!");
+///         Poll::Ready(())
+///     }
+/// }
+///
+/// let mut executor = Executor::<MyPlatform>::new();
+/// executor.spawn(Hello);
+/// executor.run();
+/// ```
+pub struct Executor<STD: Std> {
+    // Each live task with the waker that queues it again.
+    tasks: Vec<Option<(Box<Future<Output = ()>>, Waker)>>,
+    live: usize,
+    queue: Arc<ReadyQueue<STD>>,
+}
+
+impl<STD: Std> Executor<STD> {
+    /// Creates an executor for the current thread.
+    pub fn new() -> Executor<STD> {
+        Executor {
+            tasks: Vec::new(),
+            live: 0,
+            queue: Arc::new(ReadyQueue {
+                thread: STD::current_thread_id(),
+                lock: SpinLock::new(),
+                ready: UnsafeCell::new(Vec::new()),
+                _std: PhantomData,
+            }),
+        }
+    }
+
+    /// Adds a task to be polled by `run`.
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, future: F) {
+        let task = self.tasks.len();
+        let waker = Waker::new(Arc::new(TaskWaker {
+            task: task,
+            queue: self.queue.clone(),
+        }));
+        self.tasks.push(Some((Box::new(future), waker)));
+        self.live += 1;
+        self.queue.push(task);
+    }
+
+    /// Polls tasks as they are woken until all of them have completed.
+    ///
+    /// Must be called on the thread that created the executor.
+    pub fn run(&mut self) {
+        while self.live != 0 {
+            let ready = self.queue.take();
+            if ready.is_empty() {
+                STD::park_thread();
+                continue;
+            }
+
+            for task in ready {
+                let done = match self.tasks[task] {
+                    Some((ref mut future, ref waker)) => {
+                        let mut cx = Context::from_waker(waker);
+                        future.poll(&mut cx).is_ready()
+                    }
+                    None => false,
+                };
+                if done {
+                    self.tasks[task] = None;
+                    self.live -= 1;
+                }
+            }
+        }
+        self.tasks.clear();
+    }
+}
+
+impl<STD: Std> fmt::Debug for Executor<STD> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Executor").field("tasks", &self.live).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prelude::*;
+    use super::{Context, Future, Poll, Wake, Waker};
+
+    use alloc::arc::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Pending until polled `n` times, waking itself each time.
+    struct Countdown(usize);
+
+    impl Future for Countdown {
+        type Output = &'static str;
+
+        fn poll(&mut self, cx: &mut Context) -> Poll<&'static str> {
+            if self.0 == 0 {
+                return Poll::Ready("done");
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn poll_map() {
+        assert_eq!(Poll::Ready(1).map(|n| n + 1), Poll::Ready(2));
+        assert_eq!(Poll::Pending.map(|n: i32| n + 1), Poll::Pending);
+        assert!(Poll::Ready(()).is_ready());
+        assert!(Poll::Pending::<()>.is_pending());
+    }
+
+    #[test]
+    fn waker_wakes() {
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::new(counter.clone());
+        waker.wake_by_ref();
+        waker.clone().wake();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        drop(waker);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn future_forwarding() {
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::new(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future: Box<Future<Output = &'static str>> = Box::new(Countdown(2));
+        assert_eq!(future.poll(&mut cx), Poll::Pending);
+        assert_eq!((&mut future).poll(&mut cx), Poll::Pending);
+        assert_eq!(future.poll(&mut cx), Poll::Ready("done"));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+    }
+}
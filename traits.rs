@@ -17,8 +17,15 @@ use fmt::{Debug, Display};
 use borrow::Cow;
 use rc::Rc;
 use alloc::arc::Arc;
+use alloc::allocator::Layout;
+use boxed::FnBox;
+use heap;
 use path;
+use ptr;
+use slice;
 use ffi;
+use fs;
+use sys_common;
 use time::Duration;
 
 pub trait Std: Sized + Debug + Send + Sync + PartialEq + Eq + PartialOrd + Ord + Copy + Clone + Hash + 'static {
@@ -55,20 +62,69 @@ pub trait Std: Sized + Debug + Send + Sync + PartialEq + Eq + PartialOrd + Ord +
 
     type Stderr: Stdio<Self> + Write<Self>;
 
+    /// The platform's native error code, as carried by `io::Error` for OS
+    /// errors.
+    type OsError: Copy + Debug + Display + Send + Sync + 'static = i32;
+
     const UNIX_EPOCH: Self::SystemTime;
 
     /// Usually defined as `&[0]`
     fn empty_cstr() -> &'static [c_char<Self>];
 
-    fn last_os_error() -> i32;
-    fn error_string(code: i32) -> String;
+    fn last_os_error() -> Self::OsError;
+    fn error_string(code: Self::OsError) -> String;
 
     fn init();
     unsafe fn abort_internal() -> !;
+
+    /// Terminates the process with the given exit code.
+    ///
+    /// Called by `process::exit` after all cleanup has run. Defaults to
+    /// aborting for platforms without a notion of exit codes.
+    fn exit(_code: i32) -> ! {
+        unsafe { Self::abort_internal() }
+    }
+    /// Flushes the platform's buffered standard output, if it has any.
+    ///
+    /// Called by `process::exit` after the `at_exit` handlers have run.
+    /// Defaults to doing nothing, for platforms with unbuffered output.
+    fn flush_stdout() -> io::Result<(), Self> {
+        Ok(())
+    }
     unsafe fn strlen(cs: *const c_char<Self>) -> usize;
-    fn decode_error_kind(errno: i32) -> io::ErrorKind;
+    /// Maps an OS error code to its `io::ErrorKind`.
+    ///
+    /// On POSIX-like platforms ENOTDIR, EISDIR, ENOTEMPTY, EROFS, ENOSPC,
+    /// EXDEV, ELOOP, ENAMETOOLONG, ENOSYS and ENOMEM map to `NotADirectory`,
+    /// `IsADirectory`, `DirectoryNotEmpty`, `ReadOnlyFilesystem`,
+    /// `StorageFull`, `CrossesDevices`, `FilesystemLoop`, `InvalidFilename`,
+    /// `Unsupported` and `OutOfMemory` respectively. Codes without a
+    /// matching kind map to `Other`.
+    fn decode_error_kind(errno: Self::OsError) -> io::ErrorKind;
     unsafe fn thread_guard_init() -> Option<usize>;
 
+    /// Calls `f` with the name of the current thread, if it has one.
+    fn with_thread_name(f: &mut FnMut(Option<&str>)) {
+        f(None)
+    }
+
+    /// Looks up the environment variable `key`.
+    ///
+    /// Defaults to an empty environment.
+    fn getenv(_key: &ffi::OsStr<Self>) -> io::Result<Option<ffi::OsString<Self>>, Self> {
+        Ok(None)
+    }
+
+    /// The directory for temporary files.
+    ///
+    /// Defaults to `$TMPDIR`, falling back to `/tmp`.
+    fn temp_dir() -> path::PathBuf<Self> {
+        match Self::getenv(ffi::OsStr::new("TMPDIR")) {
+            Ok(Some(dir)) => path::PathBuf::from(dir),
+            _ => path::PathBuf::from("/tmp"),
+        }
+    }
+
     fn is_path_sep_byte(b: u8) -> bool;
     fn is_verbatim_path_sep(b: u8) -> bool {
         Self::is_path_sep_byte(b)
@@ -82,18 +138,195 @@ pub trait Std: Sized + Debug + Send + Sync + PartialEq + Eq + PartialOrd + Ord +
     fn stat(p: &path::Path<Self>) -> io::Result<Self::FileAttr, Self>;
     fn lstat(p: &path::Path<Self>) -> io::Result<Self::FileAttr, Self>;
     fn rename(old: &path::Path<Self>, new: &path::Path<Self>) -> io::Result<(), Self>;
-    fn copy(from: &path::Path<Self>, to: &path::Path<Self>) -> io::Result<u64, Self>;
+    /// Copies the contents and permissions of a regular file.
+    ///
+    /// The default opens both files and copies through `File`, offloading
+    /// to `File::copy_file_range` where possible.
+    fn copy(from: &path::Path<Self>, to: &path::Path<Self>) -> io::Result<u64, Self> {
+        sys_common::fs::copy(from, to)
+    }
     fn link(src: &path::Path<Self>, dst: &path::Path<Self>) -> io::Result<(), Self>;
     fn symlink(src: &path::Path<Self>, dst: &path::Path<Self>) -> io::Result<(), Self>;
     fn readlink(p: &path::Path<Self>) -> io::Result<path::PathBuf<Self>, Self>;
     fn canonicalize(p: &path::Path<Self>) -> io::Result<path::PathBuf<Self>, Self>;
     fn set_perm(p: &path::Path<Self>, perm: Self::Permissions) -> io::Result<(), Self>;
     fn rmdir(p: &path::Path<Self>) -> io::Result<(), Self>;
-    fn remove_dir_all(p: &path::Path<Self>) -> io::Result<(), Self>;
+    /// Removes a directory and everything below it.
+    ///
+    /// The default skips entries removed concurrently and never follows
+    /// symlinks it sees. It is only symlink-safe with the `*_at` hooks
+    /// below; without them it goes by path and a directory swapped for a
+    /// symlink while the tree is being removed can still be followed.
+    fn remove_dir_all(p: &path::Path<Self>) -> io::Result<(), Self> {
+        sys_common::fs::remove_dir_all(p)
+    }
+    /// Opens the directory `name` inside `dir`, or at the path `name` if
+    /// `dir` is `None`, without following a symlink at `name`. Maps to
+    /// `openat(O_DIRECTORY | O_NOFOLLOW)`.
+    ///
+    /// Returns `Ok(None)` if the platform has no handle-relative
+    /// operations, which is the default; `remove_dir_all` then goes by path,
+    /// and `lstat_at` and `unlink_at` are never called.
+    fn open_dir_at(_dir: Option<&Self::File>, _name: &path::Path<Self>)
+                   -> io::Result<Option<Self::File>, Self> {
+        Ok(None)
+    }
+    /// Queries the entry `name` inside the directory `dir` without
+    /// following symlinks. Maps to `fstatat(AT_SYMLINK_NOFOLLOW)`.
+    fn lstat_at(_dir: &Self::File, _name: &path::Path<Self>) -> io::Result<Self::FileAttr, Self> {
+        Err(const_error!(io::ErrorKind::Unsupported,
+                         "handle-relative operations are not supported"))
+    }
+    /// Removes the entry `name` inside the directory `dir`, which must be
+    /// an empty directory if `is_dir` is set. Maps to `unlinkat`, with
+    /// `AT_REMOVEDIR` for directories.
+    fn unlink_at(_dir: &Self::File, _name: &path::Path<Self>, _is_dir: bool)
+                 -> io::Result<(), Self> {
+        Err(const_error!(io::ErrorKind::Unsupported,
+                         "handle-relative operations are not supported"))
+    }
+    /// Runs a batch of operations, returning one result per operation in
+    /// submission order.
+    ///
+    /// Backends with a submission queue (such as io_uring) submit them all
+    /// at once. The default runs them one after the other.
+    fn submit_batch<'a>(ops: Vec<fs::BatchOp<'a, Self>>)
+                        -> io::Result<Vec<io::Result<fs::BatchResult<Self>, Self>>, Self> {
+        Ok(ops.into_iter().map(fs::BatchOp::execute).collect())
+    }
 
     fn memchr(needle: u8, haystack: &[u8]) -> Option<usize>;
     fn memrchr(needle: u8, haystack: &[u8]) -> Option<usize>;
 
+    // alloc
+
+    /// Size of the pages handed out by `map_pages`.
+    fn page_size() -> usize {
+        4096
+    }
+
+    /// Maps `len` bytes of zeroed, page-aligned memory.
+    ///
+    /// `len` is always a multiple of `page_size()`. Returns a null pointer
+    /// on failure, which is also what platforms without paging do.
+    unsafe fn map_pages(_len: usize) -> *mut u8 {
+        ptr::null_mut()
+    }
+
+    /// Releases pages obtained from `map_pages`.
+    unsafe fn unmap_pages(_ptr: *mut u8, _len: usize) {}
+
+    /// Allocates memory for `layout`, returning a null pointer on failure.
+    ///
+    /// Defaults to mapping whole pages per allocation.
+    unsafe fn alloc(layout: Layout) -> *mut u8 {
+        heap::map_alloc::<Self>(layout)
+    }
+
+    /// Releases memory obtained from `alloc`.
+    unsafe fn dealloc(ptr: *mut u8, layout: Layout) {
+        heap::map_dealloc::<Self>(ptr, layout)
+    }
+
+    /// Resizes a block obtained from `alloc`.
+    ///
+    /// Defaults to allocating a new block and copying the contents over.
+    unsafe fn realloc(ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        heap::realloc_fallback::<Self>(ptr, layout, new_size)
+    }
+
+    // backtrace
+
+    /// Walks the call stack of the current thread, calling `cb` with the
+    /// instruction pointer of each frame, innermost first, until it returns
+    /// `false`.
+    ///
+    /// Defaults to reporting no frames. Backends whose code is known to be
+    /// built with frame pointers can forward to
+    /// `backtrace::trace_frame_pointers`; on code without them it reads
+    /// garbage.
+    fn trace(_cb: &mut FnMut(usize) -> bool) {}
+
+    // poll
+
+    /// Creates a kernel event queue (epoll, kqueue, ...) and returns a
+    /// handle to it.
+    ///
+    /// Returns `Ok(None)` if the platform has none, which is the default;
+    /// `io::poll::Poller` then falls back to `poll`, and the other
+    /// `event_queue_*` hooks are never called.
+    fn event_queue_new() -> io::Result<Option<usize>, Self> {
+        Ok(None)
+    }
+
+    /// Starts watching `file` for `interest`, reporting its events as
+    /// `token`.
+    fn event_queue_register(_queue: usize, _file: &Self::File, _token: io::poll::Token,
+                            _interest: io::poll::Interest) -> io::Result<(), Self> {
+        Err(const_error!(io::ErrorKind::Unsupported, "event queues are not supported"))
+    }
+
+    /// Changes the interest of a file registered with `event_queue_register`.
+    fn event_queue_modify(_queue: usize, _file: &Self::File, _token: io::poll::Token,
+                          _interest: io::poll::Interest) -> io::Result<(), Self> {
+        Err(const_error!(io::ErrorKind::Unsupported, "event queues are not supported"))
+    }
+
+    /// Stops watching a file registered with `event_queue_register`.
+    fn event_queue_deregister(_queue: usize, _file: &Self::File) -> io::Result<(), Self> {
+        Err(const_error!(io::ErrorKind::Unsupported, "event queues are not supported"))
+    }
+
+    /// Waits for level-triggered readiness events, storing up to
+    /// `events.len()` of them and returning how many were stored. `None`
+    /// waits indefinitely.
+    fn event_queue_wait(_queue: usize, _events: &mut [io::poll::Event],
+                        _timeout: Option<Duration>) -> io::Result<usize, Self> {
+        Err(const_error!(io::ErrorKind::Unsupported, "event queues are not supported"))
+    }
+
+    /// Closes an event queue created by `event_queue_new`.
+    fn event_queue_close(_queue: usize) {}
+
+    /// Maps to `poll(2)`: waits until at least one of `fds` is ready or
+    /// `timeout` elapses, filling in their `ready` and `error` fields.
+    fn poll(_fds: &mut [io::poll::PollFd<Self>], _timeout: Option<Duration>)
+            -> io::Result<usize, Self> {
+        Err(const_error!(io::ErrorKind::Unsupported, "polling is not supported"))
+    }
+
+    // thread
+
+    /// Spawns a detached thread running `f`.
+    ///
+    /// Defaults to an error for platforms without threads; blocking work
+    /// offloaded by the async APIs then runs inline.
+    ///
+    /// Platforms implementing this should implement `current_thread_id`,
+    /// `park_thread` and `unpark_thread` too. Without them idle workers of
+    /// the blocking pool spin briefly and exit, and are restarted per job.
+    fn spawn_thread(_f: Box<FnBox() + Send>) -> io::Result<(), Self> {
+        Err(const_error!(io::ErrorKind::Unsupported, "threads are not supported"))
+    }
+
+    /// Identifies the calling thread for `unpark_thread` and the panic
+    /// runtime. Ids must be nonzero; the default of 0 means threads can't be
+    /// told apart.
+    fn current_thread_id() -> usize {
+        0
+    }
+
+    /// Blocks the calling thread until `unpark_thread` is called with its
+    /// id, returning right away if that already happened since the last
+    /// call. May also return spuriously.
+    ///
+    /// Defaults to returning right away, so callers end up spinning.
+    fn park_thread() {}
+
+    /// Wakes up the thread with the given id if it is parked, or makes its
+    /// next `park_thread` return right away.
+    fn unpark_thread(_id: usize) {}
+
     // rand
     // fn hashmap_random_keys() -> (u64, u64);
 
@@ -194,14 +427,124 @@ pub trait File<STD: Std>: Sized + Debug {
     fn open(path: &path::Path<STD>, opts: &STD::OpenOptions) -> io::Result<STD::File, STD>;
     fn read(&self, buf: &mut [u8]) -> io::Result<usize, STD>;
     fn write(&self, buf: &[u8]) -> io::Result<usize, STD>;
+    /// Maps to `readv`; defaults to reading into the first nonempty buffer.
+    fn read_vectored(&self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize, STD> {
+        io::default_read_vectored(|b| self.read(b), bufs)
+    }
+    /// Reads into possibly uninitialized memory.
+    ///
+    /// The default hands the uninitialized part of the cursor straight to
+    /// `read`, as the `Initializer::nop()` of `fs::File` used to, so `read`
+    /// must only ever write to its buffer.
+    fn read_buf(&self, mut cursor: io::BorrowedCursor) -> io::Result<(), STD> {
+        let n = unsafe {
+            let buf = cursor.as_mut();
+            self.read(slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len()))?
+        };
+        // SAFETY: `read` initialized the first `n` bytes.
+        unsafe {
+            cursor.set_init(n);
+            cursor.advance(n);
+        }
+        Ok(())
+    }
+    /// Maps to `writev`; defaults to writing the first nonempty buffer.
+    fn write_vectored(&self, bufs: &[io::IoSlice]) -> io::Result<usize, STD> {
+        io::default_write_vectored(|b| self.write(b), bufs)
+    }
     fn flush(&self) -> io::Result<(), STD>;
+    /// Maps to `copy_file_range`/`sendfile`: copies up to `len` bytes from
+    /// the cursor of `self` to the cursor of `dst`, advancing both.
+    ///
+    /// Returns `Ok(None)` without copying anything if the platform can't
+    /// offload copies between these files, which is the default.
+    fn copy_file_range(&self, _dst: &Self, _len: u64) -> io::Result<Option<u64>, STD> {
+        Ok(None)
+    }
     fn seek(&self, pos: io::SeekFrom) -> io::Result<u64, STD>;
+    /// Whether `read_at` and `write_at` leave the cursor alone, as `pread`
+    /// and `pwrite` do.
+    ///
+    /// Backends overriding both should set this, so `fs::File` doesn't
+    /// serialize positional calls on the same file. Defaults to `false`.
+    const NATIVE_POSITIONAL_IO: bool = false;
+    /// Maps to `pread`.
+    ///
+    /// The default emulates it by seeking to `offset` and back, which is
+    /// not safe to race with any other use of the cursor. `fs::File`
+    /// serializes its own positional calls per file unless
+    /// `NATIVE_POSITIONAL_IO` is set.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize, STD> {
+        sys_common::fs::read_at(self, buf, offset)
+    }
+    /// Maps to `pwrite`.
+    ///
+    /// The default emulates it like `read_at`, with the same caveat.
+    fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize, STD> {
+        sys_common::fs::write_at(self, buf, offset)
+    }
     fn fsync(&self) -> io::Result<(), STD>;
     fn datasync(&self) -> io::Result<(), STD>;
     fn truncate(&self, size: u64) -> io::Result<(), STD>;
     fn file_attr(&self) -> io::Result<STD::FileAttr, STD>;
     fn duplicate(&self) -> io::Result<Self, STD>;
     fn set_permissions(&self, perms: STD::Permissions) -> io::Result<(), STD>;
+    /// Whether the default locking methods succeed without doing anything
+    /// instead of failing with `Unsupported`.
+    ///
+    /// Meant for platforms where only one process can ever run, so there is
+    /// nobody to lock out. Defaults to `false`.
+    const NOOP_LOCKING: bool = false;
+    /// Maps to `flock(LOCK_SH)` or an OFD read lock: blocks until a shared
+    /// lock on the whole file is acquired.
+    ///
+    /// The locking methods default to `Unsupported` errors, or to no-ops if
+    /// `NOOP_LOCKING` is set.
+    fn lock_shared(&self) -> io::Result<(), STD> {
+        if Self::NOOP_LOCKING { Ok(()) } else { Err(no_locking()) }
+    }
+    /// Maps to `flock(LOCK_EX)` or an OFD write lock.
+    fn lock_exclusive(&self) -> io::Result<(), STD> {
+        if Self::NOOP_LOCKING { Ok(()) } else { Err(no_locking()) }
+    }
+    /// Like `lock_shared` but doesn't block, returning `Ok(false)` if the
+    /// lock is held elsewhere.
+    fn try_lock_shared(&self) -> io::Result<bool, STD> {
+        if Self::NOOP_LOCKING { Ok(true) } else { Err(no_locking()) }
+    }
+    /// Like `lock_exclusive` but doesn't block, returning `Ok(false)` if
+    /// the lock is held elsewhere.
+    fn try_lock_exclusive(&self) -> io::Result<bool, STD> {
+        if Self::NOOP_LOCKING { Ok(true) } else { Err(no_locking()) }
+    }
+    /// Maps to `flock(LOCK_UN)`.
+    fn unlock(&self) -> io::Result<(), STD> {
+        if Self::NOOP_LOCKING { Ok(()) } else { Err(no_locking()) }
+    }
+    /// Sets the access and modification times, leaving those that are
+    /// `None` unchanged.
+    ///
+    /// Defaults to an `Unsupported` error.
+    fn set_times(&self, _accessed: Option<STD::SystemTime>, _modified: Option<STD::SystemTime>)
+                 -> io::Result<(), STD> {
+        Err(const_error!(io::ErrorKind::Unsupported, "setting file times is not supported"))
+    }
+    /// Moves the file in or out of non-blocking mode, where reads and
+    /// writes that can't complete right away fail with
+    /// `ErrorKind::WouldBlock`.
+    ///
+    /// Defaults to only accepting blocking mode.
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<(), STD> {
+        if nonblocking {
+            Err(const_error!(io::ErrorKind::Unsupported, "non-blocking mode is not supported"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn no_locking<STD: Std>() -> io::Error<STD> {
+    const_error!(io::ErrorKind::Unsupported, "file locking is not supported")
 }
 
 pub trait FileAttr<STD: Std>: Sized {
@@ -211,6 +554,14 @@ pub trait FileAttr<STD: Std>: Sized {
     fn modified(&self) -> io::Result<STD::SystemTime, STD>;
     fn accessed(&self) -> io::Result<STD::SystemTime, STD>;
     fn created(&self) -> io::Result<STD::SystemTime, STD>;
+    /// Uniquely identifies the file on the system, as `(device, inode)`
+    /// does on Unix.
+    ///
+    /// Used to detect symlink loops. Defaults to `None`, in which case
+    /// canonical paths are compared instead.
+    fn file_id(&self) -> Option<(u64, u64)> {
+        None
+    }
 }
 
 pub trait OpenOptions: Sized {